@@ -0,0 +1,580 @@
+use anyhow::{anyhow, bail, Context, Result};
+use crate::jobs::{Progress, ProgressReader};
+use chrono::{DateTime, Local, NaiveDateTime};
+use std::{
+    cell::Cell,collections::{HashMap, HashSet},fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ArchiveKind {
+    Zip,Tar,TarGz,TarXz,TarZst,SevenZ,
+}
+impl ArchiveKind {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            Some(Self::TarXz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(Self::TarZst)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".zip") || name.ends_with(".jar") {
+            Some(Self::Zip)
+        } else if name.ends_with(".7z") {
+            Some(Self::SevenZ)
+        } else {
+            None
+        }
+    }
+    /// Strips the archive extension, e.g. `photos.tar.gz` becomes `photos`.
+    pub fn stem(file_name: &str) -> &str {
+        const EXTENSIONS: &[&str] = &[".tar.gz", ".tgz", ".tar.xz", ".txz", ".tar.zst", ".tzst", ".tar", ".zip", ".jar", ".7z"];
+        EXTENSIONS
+            .iter()
+            .find_map(|ext| {
+                let split = file_name.len().checked_sub(ext.len())?;
+                let matches = split > 0 && file_name.is_char_boundary(split) && file_name[split..].eq_ignore_ascii_case(ext);
+                matches.then(|| &file_name[..split])
+            })
+            .unwrap_or(file_name)
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::Tar => "tar",
+            Self::TarGz => "tar.gz",
+            Self::TarXz => "tar.xz",
+            Self::TarZst => "tar.zst",
+            Self::SevenZ => "7z",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ArchiveEntry {
+    pub path: String,pub size: u64,pub modified: Option<NaiveDateTime>,pub is_dir: bool,
+}
+
+/// Archive member names are normalized to `/`-separated relative paths
+/// without a trailing slash, so `./docs/` and `docs` compare equal.
+fn normalize_entry_path(name: &str) -> String {
+    let name = name.replace('\\', "/");
+    let mut name = name.as_str();
+    while let Some(rest) = name.strip_prefix("./") {
+        name = rest;
+    }
+    name.trim_matches('/').to_string()
+}
+fn local_time(unix_secs: i64) -> Option<NaiveDateTime> {
+    DateTime::from_timestamp(unix_secs, 0).map(|t| t.with_timezone(&Local).naive_local())
+}
+fn open_tar(file: impl Read + 'static, kind: ArchiveKind) -> Result<tar::Archive<Box<dyn Read>>> {
+    let file = io::BufReader::new(file);
+    let reader: Box<dyn Read> = match kind {
+        ArchiveKind::Tar => Box::new(file),
+        ArchiveKind::TarGz => Box::new(flate2::read::GzDecoder::new(file)),
+        ArchiveKind::TarXz => Box::new(xz2::read::XzDecoder::new(file)),
+        ArchiveKind::TarZst => Box::new(zstd::stream::read::Decoder::new(file)?),
+        _ => bail!("Not a tar archive"),
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+pub fn list_entries(path: &Path, kind: ArchiveKind) -> Result<Vec<ArchiveEntry>> {
+    let mut entries = Vec::new();
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+            for i in 0..archive.len() {
+                let file = archive.by_index(i)?;
+                entries.push(ArchiveEntry {
+                    path: normalize_entry_path(file.name()),
+                    size: file.size(),
+                    modified: file.last_modified().and_then(|t| NaiveDateTime::try_from(t).ok()),
+                    is_dir: file.is_dir(),
+                });
+            }
+        }
+        ArchiveKind::SevenZ => {
+            let archive = sevenz_rust::Archive::open(path)?;
+            for file in &archive.files {
+                entries.push(ArchiveEntry {
+                    path: normalize_entry_path(file.name()),
+                    size: file.size(),
+                    modified: if file.has_last_modified_date {
+                        local_time(file.last_modified_date().to_unix_time())
+                    } else {
+                        None
+                    },
+                    is_dir: file.is_directory(),
+                });
+            }
+        }
+        _ => {
+            let mut archive = open_tar(fs::File::open(path)?, kind)?;
+            for entry in archive.entries()? {
+                let entry = entry?;
+                let header = entry.header();
+                entries.push(ArchiveEntry {
+                    path: normalize_entry_path(&entry.path()?.to_string_lossy()),
+                    size: header.size()?,
+                    modified: header.mtime().ok().and_then(|t| local_time(t as i64)),
+                    is_dir: header.entry_type().is_dir(),
+                });
+            }
+        }
+    }
+    entries.retain(|e| !e.path.is_empty());
+    Ok(entries)
+}
+
+/// Streams every member of the archive to `each` until it returns `false`.
+/// Members the callback does not consume are skipped.
+pub fn for_each_entry<F>(path: &Path, kind: ArchiveKind, each: F) -> Result<()>
+where
+    F: FnMut(&ArchiveEntry, &mut dyn Read) -> Result<bool>,
+{
+    let file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    for_each_entry_in(file, len, kind, each)
+}
+/// [`for_each_entry`] over an already opened archive of `len` bytes.
+fn for_each_entry_in<R, F>(file: R, len: u64, kind: ArchiveKind, mut each: F) -> Result<()>
+where
+    R: Read + Seek + 'static,
+    F: FnMut(&ArchiveEntry, &mut dyn Read) -> Result<bool>,
+{
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(file)?;
+            for i in 0..archive.len() {
+                let mut file = archive.by_index(i)?;
+                let entry = ArchiveEntry {
+                    path: normalize_entry_path(file.name()),
+                    size: file.size(),
+                    modified: file.last_modified().and_then(|t| NaiveDateTime::try_from(t).ok()),
+                    is_dir: file.is_dir(),
+                };
+                if !each(&entry, &mut file)? {
+                    break;
+                }
+            }
+        }
+        ArchiveKind::SevenZ => {
+            let mut reader = sevenz_rust::SevenZReader::new(file, len, sevenz_rust::Password::empty())?;
+            let mut failure = None;
+            reader.for_each_entries(|file, data| {
+                let entry = ArchiveEntry {
+                    path: normalize_entry_path(file.name()),
+                    size: file.size(),
+                    modified: None,
+                    is_dir: file.is_directory(),
+                };
+                match each(&entry, data) {
+                    Ok(keep_going) => {
+                        // Solid blocks share one stream, so unread data must be drained.
+                        io::copy(data, &mut io::sink())?;
+                        Ok(keep_going)
+                    }
+                    Err(err) => {
+                        failure = Some(err);
+                        Ok(false)
+                    }
+                }
+            })?;
+            if let Some(err) = failure {
+                return Err(err);
+            }
+        }
+        _ => {
+            let mut archive = open_tar(file, kind)?;
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let header = entry.header();
+                // Links and special files are never extracted; only their listing is shown.
+                if !header.entry_type().is_file() && !header.entry_type().is_dir() {
+                    continue;
+                }
+                let archive_entry = ArchiveEntry {
+                    path: normalize_entry_path(&entry.path()?.to_string_lossy()),
+                    size: header.size()?,
+                    modified: header.mtime().ok().and_then(|t| local_time(t as i64)),
+                    is_dir: header.entry_type().is_dir(),
+                };
+                if !each(&archive_entry, &mut entry)? {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads at most `limit` bytes of a single member into memory.
+pub fn read_entry(path: &Path, kind: ArchiveKind, entry_path: &str, limit: u64) -> Result<Vec<u8>> {
+    let mut data = None;
+    for_each_entry(path, kind, |entry, reader| {
+        if entry.path != entry_path {
+            return Ok(true);
+        }
+        let mut buffer = Vec::new();
+        reader.take(limit).read_to_end(&mut buffer)?;
+        data = Some(buffer);
+        Ok(false)
+    })?;
+    data.with_context(|| format!("'{}' not found in archive", entry_path))
+}
+
+/// Resolves an archive member name below `dest`, refusing absolute paths and
+/// `..` components so untrusted archives cannot write outside the target.
+pub fn safe_join(dest: &Path, entry_path: &str) -> Result<PathBuf> {
+    let mut target = dest.to_path_buf();
+    for component in Path::new(entry_path).components() {
+        match component {
+            Component::Normal(part) => target.push(part),
+            Component::CurDir => {}
+            _ => bail!("Refusing unsafe archive path '{}'", entry_path),
+        }
+    }
+    Ok(target)
+}
+
+/// Extracts the member `entry_path` (a file, or a directory and everything
+/// below it) into `dest`, keeping its base name.
+pub fn extract_entry(path: &Path, kind: ArchiveKind, entry_path: &str, dest: &Path) -> Result<()> {
+    let base = match entry_path.rfind('/') {
+        Some(i) => &entry_path[..i + 1],
+        None => "",
+    };
+    let dir_prefix = format!("{}/", entry_path);
+    let mut found = false;
+    for_each_entry(path, kind, |entry, reader| {
+        if entry.path != entry_path && !entry.path.starts_with(&dir_prefix) {
+            return Ok(true);
+        }
+        found = true;
+        write_entry(&safe_join(dest, &entry.path[base.len()..])?, entry.is_dir, reader)?;
+        Ok(true)
+    })?;
+    if !found {
+        bail!("'{}' not found in archive", entry_path);
+    }
+    Ok(())
+}
+
+/// Extracts the whole archive into `dest`, returning the number of files
+/// written. Progress is measured in archive bytes read, so it is known
+/// without a separate listing pass; existing files are never overwritten.
+pub fn extract_all(path: &Path, kind: ArchiveKind, dest: &Path, progress: &mut Progress) -> Result<usize> {
+    let file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    progress.set_total(len);
+    fs::create_dir_all(dest)?;
+    let archive_read = Rc::new(Cell::new(0));
+    let file = CountingReader { inner: file, count: Rc::clone(&archive_read) };
+    let mut count = 0;
+    for_each_entry_in(file, len, kind, |entry, reader| {
+        let target = safe_join(dest, &entry.path)?;
+        let mut reader = ArchiveProgress { inner: reader, archive_read: &archive_read, progress: &mut *progress };
+        write_entry(&target, entry.is_dir, &mut reader)?;
+        if !entry.is_dir {
+            count += 1;
+        }
+        Ok(true)
+    })?;
+    Ok(count)
+}
+fn write_entry(target: &Path, is_dir: bool, reader: &mut dyn Read) -> Result<()> {
+    if is_dir {
+        fs::create_dir_all(target)?;
+    } else {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::File::create_new(target).map_err(|err| match err.kind() {
+            io::ErrorKind::AlreadyExists => anyhow!("'{}' already exists, not overwriting it", target.display()),
+            _ => err.into(),
+        })?;
+        io::copy(reader, &mut file)?;
+    }
+    Ok(())
+}
+
+/// Counts the bytes read from the archive file itself.
+struct CountingReader<R> {
+    inner: R,count: Rc<Cell<u64>>,
+}
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.set(self.count.get() + n as u64);
+        Ok(n)
+    }
+}
+impl<R: Seek> Seek for CountingReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+/// Reports how much of the archive file has been read after every read
+/// of a member.
+struct ArchiveProgress<'a, R> {
+    inner: R,archive_read: &'a Cell<u64>,progress: &'a mut Progress,
+}
+impl<R: Read> Read for ArchiveProgress<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.progress.set_done(self.archive_read.get());
+        Ok(n)
+    }
+}
+
+/// Collects `sources` and everything below them as (file, member name) pairs.
+/// Symlinked directories are stored as-is rather than followed.
+fn collect_sources(sources: &[PathBuf]) -> Result<Vec<(PathBuf, String)>> {
+    fn walk(path: &Path, name: String, out: &mut Vec<(PathBuf, String)>) -> Result<()> {
+        let is_real_dir = path.is_dir() && !path.is_symlink();
+        out.push((path.to_path_buf(), name.clone()));
+        if is_real_dir {
+            let mut children: Vec<PathBuf> = fs::read_dir(path)?.filter_map(|e| e.ok().map(|e| e.path())).collect();
+            children.sort();
+            for child in children {
+                let child_name = child.file_name().context("Failed to get file name")?.to_string_lossy().to_string();
+                walk(&child, format!("{}/{}", name, child_name), out)?;
+            }
+        }
+        Ok(())
+    }
+    let mut out = Vec::new();
+    for source in sources {
+        let name = source.file_name().context("Failed to get file name")?.to_string_lossy().to_string();
+        walk(source, name, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// Writes `sources` into a new archive at `dest`, returning the number of files added.
+/// A partially written archive is removed again if anything fails.
+pub fn compress(sources: &[PathBuf], dest: &Path, kind: ArchiveKind, progress: &mut Progress) -> Result<usize> {
+    let members = collect_sources(sources)?;
+    let output = io::BufWriter::new(fs::File::create_new(dest)?);
+    write_archive(output, &members, kind, progress).inspect_err(|_| {
+        let _ = fs::remove_file(dest);
+    })
+}
+fn write_archive(
+    output: io::BufWriter<fs::File>,members: &[(PathBuf, String)],kind: ArchiveKind,progress: &mut Progress,
+) -> Result<usize> {
+    let files: Vec<&(PathBuf, String)> = members.iter().filter(|(path, _)| path.is_file()).collect();
+    progress.set_total(files.iter().filter_map(|(path, _)| fs::metadata(path).ok()).map(|m| m.len()).sum());
+    match kind {
+        ArchiveKind::Zip => {
+            let mut zip = zip::ZipWriter::new(output);
+            let options = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated)
+                .large_file(true);
+            for (path, name) in members {
+                if path.is_file() {
+                    zip.start_file(name.as_str(), options)?;
+                    let mut reader = ProgressReader { inner: fs::File::open(path)?, progress: &mut *progress };
+                    io::copy(&mut reader, &mut zip)?;
+                } else if path.is_dir() {
+                    zip.add_directory(name.as_str(), options)?;
+                }
+            }
+            zip.finish()?.flush()?;
+        }
+        ArchiveKind::TarGz => {
+            let encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+            append_tar(tar::Builder::new(encoder), members, progress)?.finish()?.flush()?;
+        }
+        ArchiveKind::TarZst => {
+            let encoder = zstd::stream::write::Encoder::new(output, 0)?;
+            append_tar(tar::Builder::new(encoder), members, progress)?.finish()?.flush()?;
+        }
+        _ => bail!("Creating {} archives is not supported", kind.name()),
+    }
+    Ok(files.len())
+}
+fn append_tar<W: io::Write>(
+    mut builder: tar::Builder<W>,members: &[(PathBuf, String)],progress: &mut Progress,
+) -> Result<W> {
+    builder.follow_symlinks(false);
+    for (path, name) in members {
+        let metadata = fs::symlink_metadata(path)?;
+        if metadata.is_file() {
+            let mut header = tar::Header::new_gnu();
+            header.set_metadata(&metadata);
+            let reader = ProgressReader { inner: fs::File::open(path)?, progress: &mut *progress };
+            builder.append_data(&mut header, name, reader)?;
+        } else {
+            builder.append_path_with_name(path, name)?;
+        }
+    }
+    Ok(builder.into_inner()?)
+}
+
+/// Read-only view of an archive opened as a virtual directory.
+pub struct ArchiveBrowser {
+    pub archive_path: PathBuf,pub kind: ArchiveKind,pub entries: Vec<ArchiveEntry>,pub prefix: String,index: HashMap<String, usize>,dirs: HashSet<String>,
+}
+impl ArchiveBrowser {
+    pub fn open(archive_path: PathBuf, kind: ArchiveKind) -> Result<Self> {
+        let entries = list_entries(&archive_path, kind)?;
+        let mut index = HashMap::new();
+        let mut dirs = HashSet::new();
+        for (i, entry) in entries.iter().enumerate() {
+            index.insert(entry.path.clone(), i);
+            if entry.is_dir {
+                dirs.insert(entry.path.clone());
+            }
+            // Many archives omit directory members, so derive them from file paths.
+            let mut parent = entry.path.as_str();
+            while let Some(i) = parent.rfind('/') {
+                parent = &parent[..i];
+                dirs.insert(parent.to_string());
+            }
+        }
+        Ok(Self { archive_path, kind, entries, prefix: String::new(), index, dirs })
+    }
+    pub fn entry_path(&self, name: &str) -> String {
+        if self.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", self.prefix, name)
+        }
+    }
+    pub fn is_dir(&self, entry_path: &str) -> bool {
+        self.dirs.contains(entry_path)
+    }
+    pub fn entry(&self, entry_path: &str) -> Option<&ArchiveEntry> {
+        self.index.get(entry_path).map(|&i| &self.entries[i])
+    }
+    pub fn display_path(&self) -> PathBuf {
+        self.archive_path.join(&self.prefix)
+    }
+    /// Lists the immediate children of `dir` (empty for the archive root),
+    /// directories first, in the same order `App::get_files` uses.
+    pub fn children(&self, dir: &str, show_hidden: bool) -> Vec<String> {
+        let prefix = if dir.is_empty() { String::new() } else { format!("{}/", dir) };
+        let mut dirs: Vec<&str> = Vec::new();
+        let mut files: Vec<&str> = Vec::new();
+        let mut seen = HashSet::new();
+        for entry in &self.entries {
+            let Some(rest) = entry.path.strip_prefix(prefix.as_str()) else {
+                continue;
+            };
+            let (name, is_dir) = match rest.split_once('/') {
+                Some((dir, _)) => (dir, true),
+                None => (rest, entry.is_dir),
+            };
+            if name.is_empty() || (!show_hidden && name.starts_with('.')) {
+                continue;
+            }
+            if seen.insert((name, is_dir)) {
+                if is_dir { dirs.push(name) } else { files.push(name) }
+            }
+        }
+        dirs.sort_by_key(|name| name.to_lowercase());
+        files.sort_by_key(|name| name.to_lowercase());
+        dirs.into_iter().chain(files).map(str::to_string).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes the same small tree as a zip (without directory members) and
+    /// a tar.gz (with them), in a scratch directory removed on drop.
+    struct Samples {
+        dir: PathBuf,
+    }
+    impl Samples {
+        const FILES: &[(&str, &str)] = &[("readme.md", "hi"), ("docs/guide.txt", "guide"), ("docs/img/logo.svg", "<svg/>"), (".hidden", "")];
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("karu-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let mut zip = zip::ZipWriter::new(fs::File::create(dir.join("sample.zip")).unwrap());
+            for (name, content) in Self::FILES {
+                zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+                zip.write_all(content.as_bytes()).unwrap();
+            }
+            zip.finish().unwrap();
+            let encoder = flate2::write::GzEncoder::new(fs::File::create(dir.join("sample.tar.gz")).unwrap(), flate2::Compression::default());
+            let mut tar = tar::Builder::new(encoder);
+            for dir_name in ["docs/", "docs/img/"] {
+                let mut header = tar::Header::new_gnu();
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+                tar.append_data(&mut header, dir_name, io::empty()).unwrap();
+            }
+            for (name, content) in Self::FILES {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(content.len() as u64);
+                tar.append_data(&mut header, name, content.as_bytes()).unwrap();
+            }
+            tar.into_inner().unwrap().finish().unwrap();
+            Self { dir }
+        }
+    }
+    impl Drop for Samples {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn lists_entries_of_zip_and_tar_gz() {
+        let samples = Samples::new("list");
+        for (name, kind) in [("sample.zip", ArchiveKind::Zip), ("sample.tar.gz", ArchiveKind::TarGz)] {
+            let path = samples.dir.join(name);
+            assert_eq!(ArchiveKind::from_path(&path), Some(kind));
+            let entries = list_entries(&path, kind).unwrap();
+            let mut files: Vec<(&str, u64)> =
+                entries.iter().filter(|entry| !entry.is_dir).map(|entry| (entry.path.as_str(), entry.size)).collect();
+            files.sort();
+            assert_eq!(files, [(".hidden", 0), ("docs/guide.txt", 5), ("docs/img/logo.svg", 6), ("readme.md", 2)], "{}", name);
+        }
+    }
+
+    #[test]
+    fn browses_directories_inside_archives() {
+        let samples = Samples::new("browse");
+        for (name, kind) in [("sample.zip", ArchiveKind::Zip), ("sample.tar.gz", ArchiveKind::TarGz)] {
+            let mut browser = ArchiveBrowser::open(samples.dir.join(name), kind).unwrap();
+            // Directories come first, whether or not the archive stores them.
+            assert_eq!(browser.children("", true), ["docs", ".hidden", "readme.md"], "{}", name);
+            assert_eq!(browser.children("", false), ["docs", "readme.md"]);
+            assert!(browser.is_dir("docs") && browser.is_dir("docs/img"));
+            assert!(!browser.is_dir("readme.md"));
+            browser.prefix = "docs".to_string();
+            assert_eq!(browser.entry_path("guide.txt"), "docs/guide.txt");
+            assert_eq!(browser.children(&browser.prefix, true), ["img", "guide.txt"]);
+            assert_eq!(browser.children("docs/img", true), ["logo.svg"]);
+            assert_eq!(browser.entry("docs/guide.txt").map(|entry| entry.size), Some(5));
+            assert_eq!(browser.display_path(), samples.dir.join(name).join("docs"));
+        }
+    }
+
+    #[test]
+    fn safe_join_keeps_members_inside_dest() {
+        let dest = Path::new("/tmp/out");
+        assert_eq!(safe_join(dest, "a/b.txt").unwrap(), Path::new("/tmp/out/a/b.txt"));
+        assert_eq!(safe_join(dest, "a/./b").unwrap(), Path::new("/tmp/out/a/b"));
+        assert_eq!(safe_join(dest, "./a").unwrap(), Path::new("/tmp/out/a"));
+    }
+
+    #[test]
+    fn safe_join_refuses_escaping_paths() {
+        let dest = Path::new("/tmp/out");
+        assert!(safe_join(dest, "../x").is_err());
+        assert!(safe_join(dest, "a/../../x").is_err());
+        assert!(safe_join(dest, "/abs").is_err());
+    }
+}
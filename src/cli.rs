@@ -0,0 +1,126 @@
+use anyhow::{bail, Context, Result};
+use std::{ffi::OsString, path::PathBuf, process};
+
+const HELP: &str = "\
+Usage: karu [OPTIONS] [PATH]
+
+Browse PATH (default: the current directory). If PATH is a file, Karu
+opens its directory with the file selected.
+
+Options:
+      --show-hidden          Show hidden files (the default)
+      --no-hidden            Hide hidden files
+      --sort <MODE>          Sort entries by name, size, modified or extension
+      --config <FILE>        Read settings from FILE instead of the default config
+      --read-only            Browse without changing anything on disk
+      --cwd-file <FILE>      Write the last directory to FILE when quitting with q
+      --print-cwd            Print the last directory when quitting with q
+      --choose-files <FILE>  Pick files and write their paths to FILE (- for stdout)
+      --choose-dir <FILE>    Pick a directory and write its path to FILE (- for stdout)
+  -0, --null                 Separate chosen paths with NUL instead of newlines
+  -h, --help                 Print this help
+  -V, --version              Print the version
+";
+
+/// Order of entries within the directory and file groups of a listing.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SortMode {
+    Name,
+    /// Largest first.
+    Size,
+    /// Newest first.
+    Modified,
+    Extension,
+}
+
+impl SortMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "name" => Some(Self::Name),
+            "size" => Some(Self::Size),
+            "modified" | "time" => Some(Self::Modified),
+            "extension" | "ext" => Some(Self::Extension),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChooseKind {
+    Files,Dir,
+}
+
+/// `--choose-files`/`--choose-dir`: Karu runs as a picker and reports the
+/// chosen paths instead of opening them.
+pub struct Chooser {
+    pub kind: ChooseKind,
+    /// `None` for `-`, meaning stdout.
+    pub output: Option<PathBuf>,
+}
+
+/// Options given on the command line.
+#[derive(Default)]
+pub struct Args {
+    /// Where to start; the current directory if not given.
+    pub path: Option<PathBuf>,
+    pub show_hidden: Option<bool>,
+    pub sort: Option<SortMode>,
+    /// Replaces the default config file.
+    pub config: Option<PathBuf>,
+    pub read_only: bool,
+    /// Write the last directory here on quit, for shell cd-on-exit wrappers.
+    pub cwd_file: Option<PathBuf>,
+    /// Print the last directory on stdout on quit.
+    pub print_cwd: bool,
+    pub choose: Option<Chooser>,
+    /// Separate chosen paths with NUL instead of newlines.
+    pub null: bool,
+}
+
+/// Parses the command line, exiting for `--help` and `--version`.
+pub fn parse() -> Result<Args> {
+    use lexopt::prelude::*;
+    let mut args = Args::default();
+    let mut parser = lexopt::Parser::from_env();
+    while let Some(arg) = parser.next()? {
+        match arg {
+            Value(path) if args.path.is_none() => args.path = Some(path.into()),
+            Long("show-hidden") => args.show_hidden = Some(true),
+            Long("no-hidden") => args.show_hidden = Some(false),
+            Long("sort") => {
+                let mode = parser.value()?.string()?;
+                let sort = SortMode::from_name(&mode)
+                    .with_context(|| format!("invalid sort mode '{}', expected name, size, modified or extension", mode))?;
+                args.sort = Some(sort);
+            }
+            Long("config") => args.config = Some(parser.value()?.into()),
+            Long("read-only") => args.read_only = true,
+            Long("cwd-file") => args.cwd_file = Some(parser.value()?.into()),
+            Long("print-cwd") => args.print_cwd = true,
+            Long("choose-files") => set_chooser(&mut args, ChooseKind::Files, parser.value()?)?,
+            Long("choose-dir") => set_chooser(&mut args, ChooseKind::Dir, parser.value()?)?,
+            Short('0') | Long("null") => args.null = true,
+            Short('h') | Long("help") => {
+                print!("{}", HELP);
+                process::exit(0);
+            }
+            Short('V') | Long("version") => {
+                println!("karu {}", env!("CARGO_PKG_VERSION"));
+                process::exit(0);
+            }
+            _ => return Err(arg.unexpected().into()),
+        }
+    }
+    if args.print_cwd && args.choose.as_ref().is_some_and(|chooser| chooser.output.is_none()) {
+        bail!("--print-cwd can't share stdout with the chosen paths");
+    }
+    Ok(args)
+}
+fn set_chooser(args: &mut Args, kind: ChooseKind, output: OsString) -> Result<()> {
+    if args.choose.is_some() {
+        bail!("--choose-files and --choose-dir can only be given once");
+    }
+    let output = (output != "-").then(|| PathBuf::from(output));
+    args.choose = Some(Chooser { kind, output });
+    Ok(())
+}
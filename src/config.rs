@@ -0,0 +1,100 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    env,fs,io,path::{Path, PathBuf},
+};
+
+/// Settings read from `config.toml`. Every field is optional so an empty or
+/// missing file gives the defaults.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Same as `--read-only`: block every action that changes files.
+    pub read_only: bool,
+    pub previewers: Vec<PreviewerConfig>,
+    pub openers: Vec<OpenerConfig>,
+}
+
+/// An external command used to preview files it matches, e.g.
+///
+/// ```toml
+/// [[previewers]]
+/// extensions = ["pb"]
+/// command = "protoc --decode_raw < %f"
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PreviewerConfig {
+    #[serde(default)]
+    pub mime: Vec<String>,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub globs: Vec<String>,
+    pub command: String,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+fn default_timeout_ms() -> u64 {
+    2000
+}
+
+/// A program offered for opening files it matches, e.g.
+///
+/// ```toml
+/// [[openers]]
+/// mime = ["video/*"]
+/// command = "mpv %f"
+/// default = true
+/// ```
+///
+/// `%f` is the quoted path (appended when missing). `terminal = true` runs
+/// the command in Karu's terminal instead of detaching it, for editors and
+/// viewers on machines without a desktop. Openers come from the user's own
+/// config, so they still run in read-only mode.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpenerConfig {
+    #[serde(default)]
+    pub mime: Vec<String>,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub globs: Vec<String>,
+    pub command: String,
+    /// Shown in the "Open with" menu instead of the command.
+    pub name: Option<String>,
+    #[serde(default)]
+    pub terminal: bool,
+    /// Use this opener for `o` and Enter rather than the first match.
+    #[serde(default)]
+    pub default: bool,
+}
+
+/// `$XDG_CONFIG_HOME/karu/config.toml`, falling back to `~/.config` (or
+/// `%APPDATA%` on Windows).
+pub fn default_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            if cfg!(windows) {
+                env::var_os("APPDATA").map(PathBuf::from)
+            } else {
+                env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+            }
+        })?;
+    Some(base.join("karu").join("config.toml"))
+}
+
+impl Config {
+    /// Reads the config file, treating a missing file as an empty config.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err).with_context(|| format!("Cannot read {}", path.display())),
+        };
+        toml::from_str(&content).with_context(|| format!("Invalid config in {}", path.display()))
+    }
+}
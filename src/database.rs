@@ -0,0 +1,125 @@
+use anyhow::Result;
+use rusqlite::{types::ValueRef, Connection, OpenFlags};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Column names and rows of a table, each value rendered as text.
+pub type Rows = (Vec<String>, Vec<Vec<String>>);
+
+pub struct TableInfo {
+    pub name: String,pub kind: String,
+    /// `None` for views, which would have to run their query to be counted.
+    pub rows: Option<RowCount>,
+    pub sql: String,
+}
+
+#[derive(Clone, Copy)]
+pub enum RowCount {
+    Exact(u64),
+    /// From `sqlite_stat1`, as of the last `ANALYZE`.
+    Estimated(u64),
+    /// Counting stopped here and there are no statistics.
+    AtLeast(u64),
+}
+impl fmt::Display for RowCount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Exact(rows) => write!(f, "{}", rows),
+            Self::Estimated(rows) => write!(f, "~{}", rows),
+            Self::AtLeast(rows) => write!(f, "{}+", rows),
+        }
+    }
+}
+
+/// Opens `path` so that nothing, including journal recovery, can write to it.
+fn open(path: &Path) -> Result<Connection> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+    connection.pragma_update(None, "query_only", true)?;
+    Ok(connection)
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Tables and views with their row counts and `CREATE` statements.
+/// Counting stops at `MAX_COUNTED_ROWS` per table so huge databases open
+/// quickly; past that the planner's statistics are used when present.
+pub fn tables(path: &Path) -> Result<Vec<TableInfo>> {
+    const MAX_COUNTED_ROWS: u64 = 100_000;
+    let connection = open(path)?;
+    let mut statement = connection.prepare(
+        "SELECT name, type, COALESCE(sql, '') FROM sqlite_master \
+         WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%' ORDER BY name",
+    )?;
+    let mut tables = statement
+        .query_map([], |row| {
+            Ok(TableInfo { name: row.get(0)?, kind: row.get(1)?, rows: None, sql: row.get(2)? })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for table in tables.iter_mut().filter(|table| table.kind == "table") {
+        let query = format!("SELECT COUNT(*) FROM (SELECT 1 FROM {} LIMIT {})", quote_identifier(&table.name), MAX_COUNTED_ROWS + 1);
+        table.rows = match connection.query_row(&query, [], |row| row.get::<_, u64>(0)) {
+            Ok(rows) if rows <= MAX_COUNTED_ROWS => Some(RowCount::Exact(rows)),
+            Ok(_) => Some(estimated_rows(&connection, &table.name).map_or(RowCount::AtLeast(MAX_COUNTED_ROWS), RowCount::Estimated)),
+            Err(_) => None,
+        };
+    }
+    Ok(tables)
+}
+/// The row count `ANALYZE` recorded for `table`, if it was ever run.
+fn estimated_rows(connection: &Connection, table: &str) -> Option<u64> {
+    let stat: String =
+        connection.query_row("SELECT stat FROM sqlite_stat1 WHERE tbl = ?1 LIMIT 1", [table], |row| row.get(0)).ok()?;
+    stat.split_whitespace().next()?.parse().ok()
+}
+
+/// Column names and the first `limit` rows of `table`, rendered as text.
+pub fn first_rows(path: &Path, table: &str, limit: usize) -> Result<Rows> {
+    let connection = open(path)?;
+    let mut statement = connection.prepare(&format!("SELECT * FROM {} LIMIT {}", quote_identifier(table), limit))?;
+    let columns: Vec<String> = statement.column_names().into_iter().map(str::to_string).collect();
+    let mut rows = Vec::new();
+    let mut query = statement.query([])?;
+    while let Some(row) = query.next()? {
+        let mut cells = Vec::with_capacity(columns.len());
+        for index in 0..columns.len() {
+            cells.push(match row.get_ref(index)? {
+                ValueRef::Null => "NULL".to_string(),
+                ValueRef::Integer(i) => i.to_string(),
+                ValueRef::Real(f) => f.to_string(),
+                ValueRef::Text(t) => String::from_utf8_lossy(t).replace(['\n', '\r'], " "),
+                ValueRef::Blob(b) => format!("<{} byte blob>", b.len()),
+            });
+        }
+        rows.push(cells);
+    }
+    Ok((columns, rows))
+}
+
+/// State of the table browser opened with Enter on a database file.
+pub struct DatabaseView {
+    pub path: PathBuf,pub tables: Vec<TableInfo>,pub selected: usize,pub rows: Option<Result<Rows, String>>,
+}
+impl DatabaseView {
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let tables = tables(&path)?;
+        let mut view = Self { path, tables, selected: 0, rows: None };
+        view.load_rows();
+        Ok(view)
+    }
+    pub fn select(&mut self, index: usize) {
+        if index < self.tables.len() && index != self.selected {
+            self.selected = index;
+            self.load_rows();
+        }
+    }
+    fn load_rows(&mut self) {
+        const MAX_ROWS: usize = 200;
+        self.rows = self.tables.get(self.selected).map(|table| {
+            first_rows(&self.path, &table.name, MAX_ROWS).map_err(|err| err.to_string())
+        });
+    }
+}
@@ -0,0 +1,233 @@
+use crate::panics::quietly;
+use anyhow::{anyhow, bail, Context, Result};
+use calamine::Reader;
+use quick_xml::events::Event;
+use std::{fs, io::Read, path::Path};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DocumentKind {
+    Pdf,Docx,Odt,Spreadsheet,
+}
+impl DocumentKind {
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        match mime {
+            "application/pdf" => Some(Self::Pdf),
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => Some(Self::Docx),
+            "application/vnd.oasis.opendocument.text" => Some(Self::Odt),
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            | "application/vnd.oasis.opendocument.spreadsheet"
+            | "application/vnd.ms-excel" => Some(Self::Spreadsheet),
+            _ => None,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Pdf => "PDF",
+            Self::Docx => "DOCX",
+            Self::Odt => "ODT",
+            Self::Spreadsheet => "spreadsheet",
+        }
+    }
+}
+
+/// Text pulled out of a document plus a short description such as
+/// `12 pages` for the preview title.
+pub struct DocumentText {
+    pub summary: Option<String>,pub text: String,
+}
+
+pub fn extract(path: &Path, kind: DocumentKind) -> Result<DocumentText> {
+    match kind {
+        DocumentKind::Pdf => pdf_text(path),
+        DocumentKind::Docx => {
+            let (body, truncated) = zip_member(path, "word/document.xml")?;
+            let text = xml_text(&body, truncated, &[b"t"], &[b"p"], &[(b"tab", "\t"), (b"br", "\n"), (b"cr", "\n")], &[b"r"])?;
+            let pages = zip_member(path, "docProps/app.xml").ok().and_then(|(xml, _)| element_text(&xml, b"Pages"));
+            Ok(DocumentText { summary: pages.map(|pages| plural(&pages, "page")), text })
+        }
+        DocumentKind::Odt => {
+            let (content, truncated) = zip_member(path, "content.xml")?;
+            let text = xml_text(&content, truncated, &[b"p", b"h"], &[b"p", b"h"], &[(b"tab", "\t"), (b"line-break", "\n"), (b"s", " ")], &[b"p", b"h"])?;
+            let pages = zip_member(path, "meta.xml").ok().and_then(|(xml, _)| attribute_value(&xml, b"page-count"));
+            Ok(DocumentText { summary: pages.map(|pages| plural(&pages, "page")), text })
+        }
+        DocumentKind::Spreadsheet => spreadsheet_text(path),
+    }
+}
+
+fn plural(count: &str, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == "1" { "" } else { "s" })
+}
+
+fn pdf_text(path: &Path) -> Result<DocumentText> {
+    const MAX_PAGES: u32 = 50;
+    quietly(|| {
+        let mut doc = pdf_extract::Document::load(path)?;
+        if doc.is_encrypted() && doc.decrypt("").is_err() {
+            bail!("This PDF is password protected");
+        }
+        let page_count = doc.get_pages().len() as u32;
+        let mut text = String::new();
+        for page in 1..=page_count.min(MAX_PAGES) {
+            let mut page_text = String::new();
+            pdf_extract::output_doc_page(&doc, &mut pdf_extract::PlainTextOutput::new(&mut page_text), page)
+                .map_err(|err| anyhow!("{:?}", err))?;
+            text.push_str(&format!("── Page {} ──\n{}\n\n", page, page_text.trim()));
+        }
+        if page_count > MAX_PAGES {
+            text.push_str(&format!("… {} more pages", page_count - MAX_PAGES));
+        }
+        Ok(DocumentText { summary: Some(plural(&page_count.to_string(), "page")), text })
+    })
+}
+
+fn spreadsheet_text(path: &Path) -> Result<DocumentText> {
+    const MAX_ROWS: usize = 100;
+    quietly(|| {
+        let mut workbook = calamine::open_workbook_auto(path)?;
+        let names = workbook.sheet_names();
+        let mut text = String::new();
+        for name in &names {
+            let range = workbook.worksheet_range(name)?;
+            let (height, width) = range.get_size();
+            text.push_str(&format!("── {} ({} × {}) ──\n", name, height, width));
+            for row in range.rows().take(MAX_ROWS) {
+                let cells: Vec<String> = row.iter().map(|cell| cell.to_string()).collect();
+                text.push_str(cells.join("\t").trim_end());
+                text.push('\n');
+            }
+            if height > MAX_ROWS {
+                text.push_str(&format!("… {} more rows\n", height - MAX_ROWS));
+            }
+            text.push('\n');
+        }
+        Ok(DocumentText { summary: Some(plural(&names.len().to_string(), "sheet")), text })
+    })
+}
+
+/// Most of a document's XML that is read; a member that inflates past this
+/// (a zip bomb, or just a huge document) is cut off.
+const MAX_MEMBER_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Reads a member of a zip-based document, and whether it was cut off at
+/// `MAX_MEMBER_BYTES`.
+fn zip_member(path: &Path, name: &str) -> Result<(Vec<u8>, bool)> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+    let member = archive.by_name(name).with_context(|| format!("Missing {} in document", name))?;
+    let mut data = Vec::new();
+    member.take(MAX_MEMBER_BYTES + 1).read_to_end(&mut data)?;
+    let truncated = data.len() as u64 > MAX_MEMBER_BYTES;
+    data.truncate(MAX_MEMBER_BYTES as usize);
+    Ok((data, truncated))
+}
+
+/// Collects the text of an XML document body: text inside any of
+/// `text_elements`, a newline after each of `line_elements`, and for each
+/// `(element, text)` in `breaks` that text where the element sits inside
+/// one of `break_scopes` (so a DOCX `w:tab` tab stop definition is not a
+/// tab). A `truncated` document ends wherever the XML stops making sense.
+fn xml_text(
+    xml: &[u8],truncated: bool,text_elements: &[&[u8]],line_elements: &[&[u8]],breaks: &[(&[u8], &str)],break_scopes: &[&[u8]],
+) -> Result<String> {
+    let mut reader = quick_xml::Reader::from_reader(xml);
+    let mut text = String::new();
+    let mut depth = 0;
+    let mut scope_depth = 0;
+    let mut buf = Vec::new();
+    loop {
+        let event = match reader.read_event_into(&mut buf) {
+            Ok(event) => event,
+            Err(_) if truncated => break,
+            Err(err) => return Err(err.into()),
+        };
+        match event {
+            Event::Start(e) => {
+                let name = e.local_name();
+                if break_scopes.contains(&name.as_ref()) {
+                    scope_depth += 1;
+                }
+                if text_elements.contains(&name.as_ref()) {
+                    depth += 1;
+                } else if let Some((_, insert)) = breaks.iter().find(|(element, _)| name.as_ref() == *element) {
+                    if scope_depth > 0 {
+                        text.push_str(insert);
+                    }
+                }
+            }
+            Event::Empty(e) => {
+                if let Some((_, insert)) = breaks.iter().find(|(element, _)| e.local_name().as_ref() == *element) {
+                    if scope_depth > 0 {
+                        text.push_str(insert);
+                    }
+                }
+            }
+            Event::End(e) => {
+                let name = e.local_name();
+                if break_scopes.contains(&name.as_ref()) {
+                    scope_depth -= 1;
+                }
+                if text_elements.contains(&name.as_ref()) {
+                    depth -= 1;
+                }
+                if line_elements.contains(&name.as_ref()) {
+                    text.push('\n');
+                }
+            }
+            Event::Text(t) if depth > 0 => text.push_str(&t.decode()?),
+            Event::GeneralRef(r) if depth > 0 => {
+                let resolved = match r.decode()?.as_ref() {
+                    "amp" => "&",
+                    "lt" => "<",
+                    "gt" => ">",
+                    "quot" => "\"",
+                    "apos" => "'",
+                    _ => "",
+                };
+                text.push_str(resolved);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    if truncated {
+        text.push_str(&format!("\n… document cut off after {} MB of XML\n", MAX_MEMBER_BYTES / (1024 * 1024)));
+    }
+    Ok(text)
+}
+
+/// Text of the first `name` element, e.g. `<Pages>3</Pages>`.
+fn element_text(xml: &[u8], name: &[u8]) -> Option<String> {
+    let mut reader = quick_xml::Reader::from_reader(xml);
+    let mut buf = Vec::new();
+    let mut inside = false;
+    loop {
+        match reader.read_event_into(&mut buf).ok()? {
+            Event::Start(e) => inside = e.local_name().as_ref() == name,
+            Event::Text(t) if inside => return Some(t.decode().ok()?.trim().to_string()),
+            Event::Eof => return None,
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+/// Value of the first attribute called `name` on any element.
+fn attribute_value(xml: &[u8], name: &[u8]) -> Option<String> {
+    let mut reader = quick_xml::Reader::from_reader(xml);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf).ok()? {
+            Event::Start(e) | Event::Empty(e) => {
+                for attribute in e.attributes().flatten() {
+                    if attribute.key.local_name().as_ref() == name {
+                        return Some(String::from_utf8_lossy(&attribute.value).into_owned());
+                    }
+                }
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+        buf.clear();
+    }
+}
@@ -0,0 +1,140 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+/// File contents decoded for display, with what was detected about them.
+pub struct Decoded {
+    pub text: String,pub encoding: &'static str,pub line_ending: Option<&'static str>,pub lossy: bool,
+}
+impl Decoded {
+    /// `UTF-8, LF` style summary for preview titles.
+    pub fn summary(&self) -> String {
+        let mut parts = vec![self.encoding];
+        parts.extend(self.line_ending);
+        if self.lossy {
+            parts.push("lossy");
+        }
+        parts.join(", ")
+    }
+}
+
+/// Reads up to `limit` bytes of `path` and decodes them. Pass `u64::MAX`
+/// for the whole file.
+pub fn read_file(path: &Path, limit: u64) -> io::Result<Decoded> {
+    let file = fs::File::open(path)?;
+    let mut bytes = Vec::new();
+    file.take(limit.saturating_add(1)).read_to_end(&mut bytes)?;
+    let truncated = bytes.len() as u64 > limit;
+    bytes.truncate(limit as usize);
+    Ok(decode(&bytes, truncated))
+}
+
+/// Decodes `bytes` using a BOM when present, otherwise UTF-16 spotted by its
+/// NUL pattern, UTF-8 when valid, and a statistical guess for the rest.
+/// Invalid sequences become U+FFFD rather than failing. `truncated` means
+/// more data follows, so a character cut in half at the end isn't an error.
+pub fn decode(bytes: &[u8], truncated: bool) -> Decoded {
+    let (encoding, bom_length) = Encoding::for_bom(bytes)
+        .or_else(|| utf16_without_bom(bytes).map(|encoding| (encoding, 0)))
+        .unwrap_or_else(|| (guess(bytes, truncated), 0));
+    let bytes = &bytes[bom_length..];
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut text = String::with_capacity(decoder.max_utf8_buffer_length(bytes.len()).unwrap_or(bytes.len()));
+    let (_, _, lossy) = decoder.decode_to_string(bytes, &mut text, !truncated);
+    let line_ending = line_ending(&text);
+    Decoded { text, encoding: encoding.name(), line_ending, lossy }
+}
+
+pub fn utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
+    let pairs = bytes.len() / 2;
+    if pairs < 4 {
+        return None;
+    }
+    let even_nuls = bytes.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd_nuls = bytes.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+    // Mostly-ASCII UTF-16 has a NUL in nearly every high byte and almost
+    // none in the low bytes.
+    if odd_nuls * 10 >= pairs * 9 && even_nuls * 10 <= pairs {
+        Some(UTF_16LE)
+    } else if even_nuls * 10 >= pairs * 9 && odd_nuls * 10 <= pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+fn guess(bytes: &[u8], truncated: bool) -> &'static Encoding {
+    let valid_utf8 = match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        // A cut-off character at the very end of a truncated read is fine.
+        Err(err) => truncated && err.error_len().is_none(),
+    };
+    if valid_utf8 {
+        return UTF_8;
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, !truncated);
+    detector.guess(None, true)
+}
+
+fn line_ending(text: &str) -> Option<&'static str> {
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+    let cr = text.matches('\r').count() - crlf;
+    match (crlf > 0, lf > 0, cr > 0) {
+        (false, false, false) => None,
+        (true, false, false) => Some("CRLF"),
+        (false, true, false) => Some("LF"),
+        (false, false, true) => Some("CR"),
+        _ => Some("mixed line endings"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_utf8_and_reports_line_endings() {
+        let decoded = decode("héllo\r\nwörld\r\n".as_bytes(), false);
+        assert_eq!(decoded.text, "héllo\r\nwörld\r\n");
+        assert_eq!(decoded.summary(), "UTF-8, CRLF");
+        assert_eq!(decode(b"a\nb\r\n", false).line_ending, Some("mixed line endings"));
+        assert_eq!(decode(b"no newline", false).line_ending, None);
+    }
+
+    #[test]
+    fn strips_byte_order_marks() {
+        let decoded = decode(b"\xEF\xBB\xBFtext", false);
+        assert_eq!((decoded.text.as_str(), decoded.encoding), ("text", "UTF-8"));
+        let decoded = decode(b"\xFF\xFEh\0i\0", false);
+        assert_eq!((decoded.text.as_str(), decoded.encoding), ("hi", "UTF-16LE"));
+    }
+
+    #[test]
+    fn detects_utf16_without_bom() {
+        let bytes: Vec<u8> = "hello world".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let decoded = decode(&bytes, false);
+        assert_eq!((decoded.text.as_str(), decoded.encoding), ("hello world", "UTF-16BE"));
+    }
+
+    #[test]
+    fn guesses_legacy_encodings() {
+        let decoded = decode(b"Le caf\xe9 est tr\xe8s chaud, n'est-ce pas ? D\xe9j\xe0 vu.", false);
+        assert_eq!(decoded.encoding, "windows-1252");
+        assert_eq!(decoded.text, "Le café est très chaud, n'est-ce pas ? Déjà vu.");
+        assert!(!decoded.lossy);
+    }
+
+    #[test]
+    fn truncated_reads_may_end_mid_character() {
+        let bytes = "café".as_bytes();
+        let decoded = decode(&bytes[..bytes.len() - 1], true);
+        assert_eq!(decoded.encoding, "UTF-8");
+        assert_eq!(decoded.text, "caf");
+    }
+}
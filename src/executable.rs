@@ -0,0 +1,150 @@
+use anyhow::{bail, Result};
+use goblin::{elf, mach, pe, Object};
+use memmap2::Mmap;
+use std::{fs, path::Path};
+
+/// Header details of a compiled binary: label/value pairs, the shared
+/// libraries it links against and its sections with their sizes.
+pub struct ExecutableInfo {
+    pub format: String,pub fields: Vec<(&'static str, String)>,pub libraries: Vec<String>,pub sections: Vec<(String, u64)>,
+}
+
+pub fn inspect(path: &Path) -> Result<ExecutableInfo> {
+    let file = fs::File::open(path)?;
+    // Mapped rather than read: binaries can be hundreds of megabytes and
+    // only the headers and tables the parser touches get paged in.
+    // SAFETY: the map is only read, and a binary being rewritten while it
+    // is previewed at worst produces a parse error or garbled fields.
+    let data = unsafe { Mmap::map(&file)? };
+    match Object::parse(&data)? {
+        Object::Elf(elf) => Ok(elf_info(&elf)),
+        Object::PE(pe) => Ok(pe_info(&pe)),
+        Object::Mach(mach::Mach::Binary(macho)) => Ok(macho_info(&macho, None)),
+        Object::Mach(mach::Mach::Fat(fat)) => {
+            let arches: Vec<String> = fat
+                .iter_arches()
+                .flatten()
+                .map(|arch| mach_arch_name(arch.cputype, arch.cpusubtype))
+                .collect();
+            // Describe the first slice; the others differ mostly in architecture.
+            match fat.get(0)? {
+                mach::SingleArch::MachO(macho) => Ok(macho_info(&macho, Some(arches))),
+                mach::SingleArch::Archive(_) => bail!("Universal static library"),
+            }
+        }
+        Object::Archive(_) => bail!("Static library archive"),
+        _ => bail!("Not an ELF, PE or Mach-O binary"),
+    }
+}
+
+/// Whether any section holds DWARF debug information.
+fn has_dwarf<'a>(mut names: impl Iterator<Item = &'a str>) -> bool {
+    names.any(|name| name.starts_with(".debug_") || name.starts_with(".zdebug_") || name.starts_with("__debug_"))
+}
+
+fn elf_info(elf: &elf::Elf) -> ExecutableInfo {
+    let sections: Vec<(String, u64)> = elf
+        .section_headers
+        .iter()
+        .filter_map(|header| Some((elf.shdr_strtab.get_at(header.sh_name)?.to_string(), header.sh_size)))
+        .filter(|(name, _)| !name.is_empty())
+        .collect();
+    let kind = match elf.header.e_type {
+        elf::header::ET_EXEC => "Executable",
+        elf::header::ET_DYN if elf.interpreter.is_some() => "Position-independent executable",
+        elf::header::ET_DYN => "Shared library",
+        elf::header::ET_REL => "Relocatable object",
+        elf::header::ET_CORE => "Core dump",
+        other => elf::header::et_to_str(other),
+    };
+    let format = format!(
+        "ELF {}-bit {}",
+        if elf.is_64 { 64 } else { 32 },
+        if elf.little_endian { "LSB" } else { "MSB" }
+    );
+    let mut fields = vec![
+        ("Type", kind.to_string()),
+        ("Architecture", elf::header::machine_to_str(elf.header.e_machine).to_string()),
+        ("Entry point", format!("{:#x}", elf.entry)),
+    ];
+    fields.extend(elf.interpreter.map(|interpreter| ("Interpreter", interpreter.to_string())));
+    fields.extend(elf.soname.map(|soname| ("Soname", soname.to_string())));
+    let debug = if has_dwarf(sections.iter().map(|(name, _)| name.as_str())) {
+        "DWARF"
+    } else if sections.iter().any(|(name, _)| name == ".symtab") {
+        "None (symbols only)"
+    } else {
+        "None (stripped)"
+    };
+    fields.push(("Debug info", debug.to_string()));
+    let libraries = elf.libraries.iter().map(|library| library.to_string()).collect();
+    ExecutableInfo { format, fields, libraries, sections }
+}
+
+fn pe_info(pe: &pe::PE) -> ExecutableInfo {
+    let sections: Vec<(String, u64)> = pe
+        .sections
+        .iter()
+        .map(|section| (section.name().unwrap_or("?").to_string(), section.virtual_size.max(section.size_of_raw_data) as u64))
+        .collect();
+    let format = if pe.is_64 { "PE32+" } else { "PE32" }.to_string();
+    let pdb = pe
+        .debug_data
+        .as_ref()
+        .and_then(|debug| debug.codeview_pdb70_debug_info.as_ref())
+        .map(|info| String::from_utf8_lossy(info.filename).trim_end_matches('\0').to_string());
+    let debug = match pdb {
+        Some(pdb) => format!("PDB ({})", pdb),
+        None if has_dwarf(sections.iter().map(|(name, _)| name.as_str())) => "DWARF".to_string(),
+        None => "None".to_string(),
+    };
+    let fields = vec![
+        ("Type", if pe.is_lib { "DLL" } else { "Executable" }.to_string()),
+        ("Architecture", pe::header::machine_to_str(pe.header.coff_header.machine).to_string()),
+        ("Entry point", format!("{:#x}", pe.image_base + pe.entry as u64)),
+        ("Debug info", debug),
+    ];
+    let libraries = pe.libraries.iter().map(|library| library.to_string()).collect();
+    ExecutableInfo { format, fields, libraries, sections }
+}
+
+fn mach_arch_name(cputype: u32, cpusubtype: u32) -> String {
+    // The top byte of the subtype holds capability flags, not the model.
+    mach::constants::cputype::get_arch_name_from_types(cputype, cpusubtype & 0x00ff_ffff)
+        .map_or_else(|| format!("CPU type {}", cputype), str::to_string)
+}
+
+fn macho_info(macho: &mach::MachO, universal: Option<Vec<String>>) -> ExecutableInfo {
+    let mut sections = Vec::new();
+    let mut dwarf_segment = false;
+    for segment in macho.segments.iter() {
+        dwarf_segment |= segment.name().is_ok_and(|name| name == "__DWARF");
+        for (section, _) in segment.sections().unwrap_or_default() {
+            let name = format!("{},{}", section.segname().unwrap_or("?"), section.name().unwrap_or("?"));
+            sections.push((name, section.size));
+        }
+    }
+    let format = match &universal {
+        Some(arches) => format!("Mach-O universal ({})", arches.join(", ")),
+        None => format!("Mach-O {}-bit", if macho.is_64 { 64 } else { 32 }),
+    };
+    let kind = match macho.header.filetype {
+        mach::header::MH_EXECUTE => "Executable",
+        mach::header::MH_DYLIB => "Dynamic library",
+        mach::header::MH_BUNDLE => "Bundle",
+        mach::header::MH_OBJECT => "Object file",
+        mach::header::MH_CORE => "Core dump",
+        mach::header::MH_DSYM => "Debug symbols",
+        other => mach::header::filetype_to_str(other),
+    };
+    let has_debug = dwarf_segment || has_dwarf(sections.iter().filter_map(|(name, _)| name.split(',').nth(1)));
+    let fields = vec![
+        ("Type", kind.to_string()),
+        ("Architecture", mach_arch_name(macho.header.cputype, macho.header.cpusubtype)),
+        ("Entry point", format!("{:#x}", macho.entry)),
+        ("Debug info", if has_debug { "DWARF" } else { "None" }.to_string()),
+    ];
+    // `libs` starts with "self", standing for the binary itself.
+    let libraries = macho.libs.iter().filter(|library| **library != "self").map(|library| library.to_string()).collect();
+    ExecutableInfo { format, fields, libraries, sections }
+}
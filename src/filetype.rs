@@ -0,0 +1,306 @@
+use nerd_font_symbols::md;
+use std::{
+    collections::HashMap,fs,
+    io::Read,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Category {
+    Directory,Text,Image,Audio,Video,Archive,Document,Database,Font,Executable,Binary,
+}
+
+/// What a file contains, decided from its first bytes with the extension
+/// as a hint for formats that have no magic number (mostly text).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FileType {
+    pub mime: &'static str,pub category: Category,
+}
+impl FileType {
+    pub const DIRECTORY: Self = Self { mime: "inode/directory", category: Category::Directory };
+    const TEXT: Self = Self { mime: "text/plain", category: Category::Text };
+    pub const BINARY: Self = Self { mime: "application/octet-stream", category: Category::Binary };
+
+    /// Icon-level guess for listings where reading every file is too slow.
+    pub fn guess(path: &Path, is_dir: bool) -> Self {
+        if is_dir {
+            Self::DIRECTORY
+        } else {
+            from_name(path).unwrap_or(Self::BINARY)
+        }
+    }
+    pub fn is_text(self) -> bool {
+        self.category == Category::Text
+    }
+    /// Short name for the type column, e.g. `png`, `json` or `text`.
+    pub fn label(self) -> &'static str {
+        match self.mime {
+            "text/plain" => "text",
+            "application/octet-stream" => "binary",
+            "inode/directory" => "dir",
+            mime => {
+                let subtype = mime.rsplit('/').next().unwrap_or(mime);
+                let subtype = subtype.strip_prefix("x-").unwrap_or(subtype);
+                subtype.strip_prefix("vnd.").unwrap_or(subtype)
+            }
+        }
+    }
+    pub fn glyph(self) -> &'static str {
+        let glyph = match self.category {
+            Category::Directory => md::MD_FOLDER_OPEN,
+            Category::Image => md::MD_FILE_IMAGE,
+            Category::Audio => md::MD_FILE_MUSIC,
+            Category::Video => md::MD_FILE_VIDEO,
+            Category::Archive => md::MD_ZIP_BOX,
+            Category::Database => md::MD_DATABASE,
+            Category::Font => md::MD_FORMAT_FONT,
+            Category::Executable => md::MD_APPLICATION,
+            Category::Document => match self.mime {
+                "application/pdf" => md::MD_FILE_PDF_BOX,
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => md::MD_FILE_EXCEL,
+                _ => md::MD_FILE_DOCUMENT,
+            },
+            Category::Text => match self.mime {
+                "application/json" => md::MD_CODE_JSON,
+                "text/markdown" => md::MD_LANGUAGE_MARKDOWN,
+                "text/csv" | "text/tab-separated-values" => md::MD_FILE_DELIMITED,
+                "application/toml" | "application/yaml" => md::MD_FILE_COG,
+                _ => md::MD_FILE,
+            },
+            Category::Binary => md::MD_FILE,
+        };
+        glyph.trim()
+    }
+}
+
+/// Types for formats recognised by name only. Content sniffing still has
+/// the final word, so a renamed binary is not treated as JSON.
+const EXTENSIONS: &[(&str, &str, Category)] = &[
+    ("txt", "text/plain", Category::Text),
+    ("log", "text/plain", Category::Text),
+    ("md", "text/markdown", Category::Text),
+    ("markdown", "text/markdown", Category::Text),
+    ("mdown", "text/markdown", Category::Text),
+    ("mkd", "text/markdown", Category::Text),
+    ("json", "application/json", Category::Text),
+    ("geojson", "application/json", Category::Text),
+    ("jsonc", "application/json", Category::Text),
+    ("webmanifest", "application/json", Category::Text),
+    ("yaml", "application/yaml", Category::Text),
+    ("yml", "application/yaml", Category::Text),
+    ("toml", "application/toml", Category::Text),
+    ("csv", "text/csv", Category::Text),
+    ("tsv", "text/tab-separated-values", Category::Text),
+    ("tab", "text/tab-separated-values", Category::Text),
+    ("html", "text/html", Category::Text),
+    ("htm", "text/html", Category::Text),
+    ("xml", "text/xml", Category::Text),
+    ("svg", "image/svg+xml", Category::Text),
+    ("css", "text/css", Category::Text),
+    ("js", "text/javascript", Category::Text),
+    ("ts", "text/x-typescript", Category::Text),
+    ("rs", "text/x-rust", Category::Text),
+    ("py", "text/x-python", Category::Text),
+    ("c", "text/x-c", Category::Text),
+    ("h", "text/x-c", Category::Text),
+    ("cpp", "text/x-c++", Category::Text),
+    ("go", "text/x-go", Category::Text),
+    ("java", "text/x-java", Category::Text),
+    ("sh", "text/x-shellscript", Category::Text),
+    ("ini", "text/x-ini", Category::Text),
+    ("png", "image/png", Category::Image),
+    ("jpg", "image/jpeg", Category::Image),
+    ("jpeg", "image/jpeg", Category::Image),
+    ("gif", "image/gif", Category::Image),
+    ("bmp", "image/bmp", Category::Image),
+    ("ico", "image/vnd.microsoft.icon", Category::Image),
+    ("tif", "image/tiff", Category::Image),
+    ("tiff", "image/tiff", Category::Image),
+    ("webp", "image/webp", Category::Image),
+    ("mp3", "audio/mpeg", Category::Audio),
+    ("flac", "audio/x-flac", Category::Audio),
+    ("ogg", "audio/ogg", Category::Audio),
+    ("wav", "audio/x-wav", Category::Audio),
+    ("m4a", "audio/m4a", Category::Audio),
+    ("mp4", "video/mp4", Category::Video),
+    ("mkv", "video/x-matroska", Category::Video),
+    ("webm", "video/webm", Category::Video),
+    ("mov", "video/quicktime", Category::Video),
+    ("avi", "video/x-msvideo", Category::Video),
+    ("zip", "application/zip", Category::Archive),
+    ("jar", "application/java-archive", Category::Archive),
+    ("tar", "application/x-tar", Category::Archive),
+    ("gz", "application/gzip", Category::Archive),
+    ("tgz", "application/gzip", Category::Archive),
+    ("xz", "application/x-xz", Category::Archive),
+    ("txz", "application/x-xz", Category::Archive),
+    ("zst", "application/zstd", Category::Archive),
+    ("tzst", "application/zstd", Category::Archive),
+    ("7z", "application/x-7z-compressed", Category::Archive),
+    ("pdf", "application/pdf", Category::Document),
+    ("docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document", Category::Document),
+    ("xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", Category::Document),
+    ("odt", "application/vnd.oasis.opendocument.text", Category::Document),
+    ("ods", "application/vnd.oasis.opendocument.spreadsheet", Category::Document),
+    ("xls", "application/vnd.ms-excel", Category::Document),
+    ("db", "application/vnd.sqlite3", Category::Database),
+    ("sqlite", "application/vnd.sqlite3", Category::Database),
+    ("sqlite3", "application/vnd.sqlite3", Category::Database),
+    ("db3", "application/vnd.sqlite3", Category::Database),
+    ("ttf", "font/ttf", Category::Font),
+    ("otf", "font/otf", Category::Font),
+    ("woff", "font/woff", Category::Font),
+    ("woff2", "font/woff2", Category::Font),
+    ("exe", "application/vnd.microsoft.portable-executable", Category::Executable),
+    ("dll", "application/vnd.microsoft.portable-executable", Category::Executable),
+];
+
+/// Type implied by the file name alone, used where the content can't be
+/// read cheaply, such as entries inside an archive.
+pub fn from_name(path: &Path) -> Option<FileType> {
+    if let Some("Cargo.lock" | "Pipfile") = path.file_name().and_then(|name| name.to_str()) {
+        return Some(FileType { mime: "application/toml", category: Category::Text });
+    }
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    EXTENSIONS
+        .iter()
+        .find(|(e, _, _)| *e == ext)
+        .map(|&(_, mime, category)| FileType { mime, category })
+}
+
+pub fn detect(path: &Path) -> FileType {
+    if path.is_dir() {
+        return FileType::DIRECTORY;
+    }
+    let mut head = Vec::with_capacity(8192);
+    if let Ok(file) = fs::File::open(path) {
+        let _ = file.take(8192).read_to_end(&mut head);
+    }
+    detect_bytes(&head, path)
+}
+
+/// Classifies `head`, the first few KiB of a file named `path`.
+pub fn detect_bytes(head: &[u8], path: &Path) -> FileType {
+    let hint = from_name(path);
+    if let Some(kind) = infer::get(head) {
+        let category = match kind.matcher_type() {
+            infer::MatcherType::Image => Category::Image,
+            infer::MatcherType::Audio => Category::Audio,
+            infer::MatcherType::Video => Category::Video,
+            infer::MatcherType::Archive if kind.mime_type() == "application/vnd.sqlite3" => Category::Database,
+            infer::MatcherType::Archive => Category::Archive,
+            infer::MatcherType::Book | infer::MatcherType::Doc => Category::Document,
+            infer::MatcherType::Font => Category::Font,
+            infer::MatcherType::App => Category::Executable,
+            infer::MatcherType::Text => Category::Text,
+            infer::MatcherType::Custom => Category::Binary,
+        };
+        // Containers like zip back several formats; a more specific name wins.
+        return match hint {
+            Some(hint) if hint.category == Category::Document && category == Category::Archive => hint,
+            Some(hint) if category == Category::Text && hint.is_text() => hint,
+            _ => FileType { mime: kind.mime_type(), category },
+        };
+    }
+    if looks_like_text(head) {
+        return hint.filter(|hint| hint.is_text()).unwrap_or(FileType::TEXT);
+    }
+    if head.is_empty() {
+        return hint.unwrap_or(FileType::TEXT);
+    }
+    FileType::BINARY
+}
+
+/// Text in any common encoding: a Unicode BOM, UTF-16 without one (every
+/// other byte NUL) or content with no NULs and few control characters.
+fn looks_like_text(head: &[u8]) -> bool {
+    if head.is_empty() {
+        return false;
+    }
+    if encoding_rs::Encoding::for_bom(head).is_some() || crate::encoding::utf16_without_bom(head).is_some() {
+        return true;
+    }
+    if head.contains(&0) {
+        return false;
+    }
+    let control = head
+        .iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\n' | b'\r' | b'\t' | 0x0C | 0x1B))
+        .count();
+    control * 100 <= head.len()
+}
+
+/// Detected types for listed files, re-checked when a file changes.
+#[derive(Default)]
+pub struct FileTypeCache {
+    types: HashMap<PathBuf, CachedType>,
+    /// Bumped on every lookup, so the least recently used entry can be evicted.
+    clock: u64,
+}
+struct CachedType {
+    modified: Option<SystemTime>,file_type: FileType,used: u64,
+}
+impl FileTypeCache {
+    pub fn get(&self, path: &Path) -> Option<FileType> {
+        self.types.get(path).map(|cached| cached.file_type)
+    }
+    pub fn update(&mut self, path: &Path) -> FileType {
+        const MAX_ENTRIES: usize = 4096;
+        self.clock += 1;
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        if let Some(cached) = self.types.get_mut(path) {
+            if cached.modified == modified {
+                cached.used = self.clock;
+                return cached.file_type;
+            }
+        }
+        if self.types.len() >= MAX_ENTRIES && !self.types.contains_key(path) {
+            let oldest = self.types.iter().min_by_key(|(_, cached)| cached.used).map(|(path, _)| path.clone());
+            if let Some(oldest) = oldest {
+                self.types.remove(&oldest);
+            }
+        }
+        let file_type = detect(path);
+        self.types.insert(path.to_path_buf(), CachedType { modified, file_type, used: self.clock });
+        file_type
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magic_numbers_win_over_names() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        assert_eq!(detect_bytes(png, Path::new("image.txt")), FileType { mime: "image/png", category: Category::Image });
+    }
+
+    #[test]
+    fn names_refine_generic_containers() {
+        let zip = b"PK\x03\x04\x14\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0";
+        assert_eq!(detect_bytes(zip, Path::new("a.zip")).category, Category::Archive);
+        assert_eq!(detect_bytes(zip, Path::new("a.docx")).category, Category::Document);
+    }
+
+    #[test]
+    fn text_uses_the_name_as_a_hint() {
+        assert_eq!(detect_bytes(b"{\"a\": 1}\n", Path::new("a.json")).mime, "application/json");
+        assert_eq!(detect_bytes(b"plain words\n", Path::new("a.json.bak")), FileType::TEXT);
+        // A text-looking name can't make binary content text.
+        assert_eq!(detect_bytes(b"\0\x01\x02\x03binary", Path::new("a.txt")), FileType::BINARY);
+    }
+
+    #[test]
+    fn empty_files_follow_their_name() {
+        assert_eq!(detect_bytes(b"", Path::new("notes.md")).mime, "text/markdown");
+        assert_eq!(detect_bytes(b"", Path::new("no_extension")), FileType::TEXT);
+    }
+
+    #[test]
+    fn utf16_is_text() {
+        let bytes: Vec<u8> = "hello world".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert!(detect_bytes(&bytes, Path::new("a.log")).is_text());
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::Result;
+use regex::Regex;
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// Lines kept in memory; older ones scroll away as the file grows.
+const MAX_LINES: usize = 5000;
+/// How much of the end of the file is shown when following starts.
+const INITIAL_TAIL_BYTES: u64 = 64 * 1024;
+/// Most read per poll, so a burst of output is taken in over a few frames.
+const MAX_READ_BYTES: u64 = 4 * 1024 * 1024;
+/// Longest unfinished line kept waiting for its newline; output that never
+/// ends a line (progress bars, binary data) is shown in pieces this long.
+const MAX_PARTIAL_BYTES: usize = 4 * 1024;
+
+pub enum FollowLine {
+    Text(String),
+    /// A note about the file itself, such as it being truncated.
+    Event(String),
+}
+
+/// A log file being watched like `tail -F`: new lines are appended as the
+/// file grows, and truncation or replacement by a new file (log rotation)
+/// starts over from the top of the new contents.
+pub struct Follower {
+    pub path: PathBuf,pub lines: VecDeque<FollowLine>,pub paused: bool,pub highlight: Option<Regex>,file: fs::File,offset: u64,partial: Vec<u8>,
+}
+impl Follower {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut file = fs::File::open(path)?;
+        let length = file.metadata()?.len();
+        let start = length.saturating_sub(INITIAL_TAIL_BYTES);
+        file.seek(SeekFrom::Start(start))?;
+        let mut follower = Self {
+            path: path.to_path_buf(),lines: VecDeque::new(),paused: false,highlight: None,file,offset: start,partial: Vec::new(),
+        };
+        follower.read_new()?;
+        // Starting mid-file almost always cuts the first line in half.
+        if start > 0 && !follower.lines.is_empty() {
+            follower.lines.pop_front();
+        }
+        Ok(follower)
+    }
+    /// Picks up whatever was written since the last call. Returns whether
+    /// anything changed. Does nothing while paused, so resuming catches up.
+    pub fn poll(&mut self) -> Result<bool> {
+        if self.paused {
+            return Ok(false);
+        }
+        let mut changed = self.read_new()?;
+        let Ok(current) = fs::metadata(&self.path) else {
+            // Between a rotation's rename and the new file appearing.
+            return Ok(changed);
+        };
+        if !same_file(&self.file.metadata()?, &current) {
+            self.file = fs::File::open(&self.path)?;
+            self.restart("file was replaced");
+            changed |= self.read_new()?;
+        } else if current.len() < self.offset {
+            self.file.seek(SeekFrom::Start(0))?;
+            self.restart("file was truncated");
+            changed |= self.read_new()?;
+        }
+        Ok(changed)
+    }
+    fn restart(&mut self, reason: &str) {
+        self.flush_partial();
+        self.offset = 0;
+        self.push(FollowLine::Event(format!("── {} ──", reason)));
+    }
+    fn read_new(&mut self) -> Result<bool> {
+        let mut data = Vec::new();
+        (&mut self.file).take(MAX_READ_BYTES).read_to_end(&mut data)?;
+        if data.is_empty() {
+            return Ok(false);
+        }
+        self.offset += data.len() as u64;
+        self.partial.extend_from_slice(&data);
+        if let Some(end) = self.partial.iter().rposition(|&b| b == b'\n') {
+            let complete: Vec<u8> = self.partial.drain(..=end).collect();
+            for line in complete[..complete.len() - 1].split(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(line);
+                self.push(FollowLine::Text(line.trim_end_matches('\r').to_string()));
+            }
+        }
+        if self.partial.len() > MAX_PARTIAL_BYTES {
+            let partial = std::mem::take(&mut self.partial);
+            let mut pieces = partial.chunks(MAX_PARTIAL_BYTES);
+            let last = pieces.next_back().unwrap_or_default();
+            for piece in pieces {
+                self.push(FollowLine::Text(String::from_utf8_lossy(piece).into_owned()));
+            }
+            self.partial = last.to_vec();
+        }
+        Ok(true)
+    }
+    fn flush_partial(&mut self) {
+        if !self.partial.is_empty() {
+            let line = String::from_utf8_lossy(&self.partial).into_owned();
+            self.partial.clear();
+            self.push(FollowLine::Text(line));
+        }
+    }
+    fn push(&mut self, line: FollowLine) {
+        if self.lines.len() >= MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+    /// The line still being written, shown after the complete ones.
+    pub fn pending(&self) -> Option<String> {
+        (!self.partial.is_empty()).then(|| String::from_utf8_lossy(&self.partial).into_owned())
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+/// Without inode numbers a replaced file shows up as a new creation time.
+#[cfg(not(unix))]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    a.created().ok() == b.created().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn output_without_newlines_is_split_into_pieces() {
+        let path = std::env::temp_dir().join(format!("karu-follow-{}.log", std::process::id()));
+        fs::write(&path, "start\n").unwrap();
+        let mut follower = Follower::open(&path).unwrap();
+        let progress = "=".repeat(MAX_PARTIAL_BYTES * 2 + 10);
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(progress.as_bytes()).unwrap();
+        assert!(follower.poll().unwrap());
+        fs::remove_file(&path).unwrap();
+        let lengths: Vec<usize> = follower
+            .lines
+            .iter()
+            .map(|line| match line {
+                FollowLine::Text(text) => text.len(),
+                FollowLine::Event(_) => 0,
+            })
+            .collect();
+        assert_eq!(lengths, [5, MAX_PARTIAL_BYTES, MAX_PARTIAL_BYTES]);
+        assert_eq!(follower.pending().map(|rest| rest.len()), Some(10));
+    }
+}
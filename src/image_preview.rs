@@ -0,0 +1,431 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use exif::{In, Tag, Value};
+use image::{imageops::FilterType, DynamicImage, GenericImageView, ImageDecoder, ImageReader};
+use ratatui::{buffer::Buffer, layout::Rect, style::Color};
+use resvg::{tiny_skia, usvg};
+use std::{
+    env,fs,
+    io::{BufReader, Cursor},
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+
+/// Deletes every kitty image placement on screen.
+pub const KITTY_DELETE_ALL: &str = "\x1b_Ga=d,d=A,q=2\x1b\\";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Protocol {
+    Kitty,Iterm2,Sixel,Halfblocks,
+}
+impl Protocol {
+    /// Picks the best protocol the terminal is known to support. Set
+    /// `KARU_IMAGE_PROTOCOL` to `kitty`, `iterm2`, `sixel` or `halfblocks`
+    /// to override the guess.
+    pub fn detect() -> Self {
+        if let Ok(forced) = env::var("KARU_IMAGE_PROTOCOL") {
+            match forced.to_lowercase().as_str() {
+                "kitty" => return Self::Kitty,
+                "iterm2" => return Self::Iterm2,
+                "sixel" => return Self::Sixel,
+                "halfblocks" => return Self::Halfblocks,
+                _ => {}
+            }
+        }
+        // Multiplexers swallow graphics escapes unless passthrough is configured.
+        if env::var_os("TMUX").is_some() || env::var("TERM").is_ok_and(|term| term.starts_with("screen")) {
+            return Self::Halfblocks;
+        }
+        let term = env::var("TERM").unwrap_or_default();
+        let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+        if env::var_os("KITTY_WINDOW_ID").is_some() || term.contains("kitty") || term.contains("ghostty") {
+            Self::Kitty
+        } else if matches!(term_program.as_str(), "iTerm.app" | "WezTerm") {
+            Self::Iterm2
+        } else if env::var_os("KONSOLE_VERSION").is_some()
+            || ["foot", "mlterm", "contour", "sixel"].iter().any(|name| term.contains(name))
+        {
+            Self::Sixel
+        } else {
+            Self::Halfblocks
+        }
+    }
+}
+
+enum Encoded {
+    /// Upper and lower pixel colors of every cell, row by row.
+    Halfblocks { width: u16, cells: Vec<(Color, Color)> },
+    /// A complete escape sequence drawn from the top-left cell of the area.
+    Escape(String),
+}
+
+struct CachedImage {
+    path: PathBuf,area: Rect,encoded: Encoded,
+}
+
+/// Image preview state: owns the encoded image for the current selection and
+/// tracks what has to be cleaned up on screen when it goes away.
+pub struct ImagePreview {
+    pub protocol: Protocol,font_size: (u16, u16),cache: Option<CachedImage>,kitty_on_screen: bool,drawn_this_frame: bool,
+}
+impl ImagePreview {
+    pub fn new() -> Self {
+        let font_size = crossterm::terminal::window_size()
+            .ok()
+            .filter(|size| size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0)
+            .map(|size| (size.width / size.columns, size.height / size.rows))
+            .unwrap_or((10, 20));
+        Self { protocol: Protocol::detect(), font_size, cache: None, kitty_on_screen: false, drawn_this_frame: false }
+    }
+    pub fn begin_frame(&mut self) {
+        self.drawn_this_frame = false;
+    }
+    /// Escape sequence that must be written after the frame, if an image
+    /// that lives outside the cell grid was not drawn this time.
+    pub fn end_frame(&mut self) -> Option<&'static str> {
+        if self.kitty_on_screen && !self.drawn_this_frame {
+            self.kitty_on_screen = false;
+            return Some(KITTY_DELETE_ALL);
+        }
+        None
+    }
+    /// Whether drawing over the image area (e.g. with a popup) would be
+    /// hidden or garbled by an image drawn outside ratatui's cell grid.
+    pub fn uses_escapes(&self) -> bool {
+        self.protocol != Protocol::Halfblocks
+    }
+    pub fn render(&mut self, path: &Path, area: Rect, buf: &mut Buffer) -> Result<()> {
+        let (font_width, font_height) = self.font_size;
+        self.render_with(path, area, buf, || {
+            if is_svg(path) {
+                rasterize_svg(path, (area.width * font_width) as u32, (area.height * font_height) as u32)
+            } else {
+                load_oriented(path)
+            }
+        })
+    }
+    /// Draws an image that isn't a file of its own, such as cover art
+    /// embedded in `path`. `load` only runs when the cached image is stale.
+    pub fn render_with(&mut self, path: &Path, area: Rect, buf: &mut Buffer, load: impl FnOnce() -> Result<DynamicImage>) -> Result<()> {
+        let cached = matches!(&self.cache, Some(cached) if cached.path == path && cached.area == area);
+        if !cached {
+            let img = load()?;
+            let encoded = self.encode(&img, area)?;
+            self.cache = Some(CachedImage { path: path.to_path_buf(), area, encoded });
+        }
+        let Some(cached) = &self.cache else {
+            return Ok(());
+        };
+        match &cached.encoded {
+            Encoded::Halfblocks { width, cells } => {
+                for (i, (upper, lower)) in cells.iter().enumerate() {
+                    let x = area.x + (i as u16 % width);
+                    let y = area.y + (i as u16 / width);
+                    if let Some(cell) = buf.cell_mut((x, y)) {
+                        cell.set_symbol("▀").set_fg(*upper).set_bg(*lower);
+                    }
+                }
+            }
+            Encoded::Escape(sequence) => {
+                // The whole image is emitted from the first cell; the rest of the
+                // area is skipped so ratatui never paints over the pixels.
+                for y in area.top()..area.bottom() {
+                    for x in area.left()..area.right() {
+                        if let Some(cell) = buf.cell_mut((x, y)) {
+                            cell.set_skip(true);
+                        }
+                    }
+                }
+                if let Some(cell) = buf.cell_mut((area.x, area.y)) {
+                    cell.set_skip(false).set_symbol(sequence);
+                }
+                if self.protocol == Protocol::Kitty {
+                    self.kitty_on_screen = true;
+                }
+            }
+        }
+        self.drawn_this_frame = true;
+        Ok(())
+    }
+    fn encode(&self, img: &DynamicImage, area: Rect) -> Result<Encoded> {
+        let (font_width, font_height) = (self.font_size.0 as u32, self.font_size.1 as u32);
+        if self.protocol == Protocol::Halfblocks {
+            let resized = fit(img, area.width as u32, area.height as u32 * 2);
+            let (width, height) = resized.dimensions();
+            let rgba = resized.to_rgba8();
+            let pixel = |x: u32, y: u32| {
+                if y >= height {
+                    return Color::Reset;
+                }
+                let [r, g, b, a] = rgba.get_pixel(x, y).0;
+                if a < 128 { Color::Reset } else { Color::Rgb(r, g, b) }
+            };
+            let mut cells = Vec::new();
+            for row in 0..height.div_ceil(2) {
+                for x in 0..width {
+                    cells.push((pixel(x, row * 2), pixel(x, row * 2 + 1)));
+                }
+            }
+            return Ok(Encoded::Halfblocks { width: width as u16, cells });
+        }
+        let resized = fit(img, area.width as u32 * font_width, area.height as u32 * font_height);
+        let sequence = match self.protocol {
+            Protocol::Kitty => {
+                let data = STANDARD.encode(png_bytes(&resized)?);
+                let mut sequence = KITTY_DELETE_ALL.to_string();
+                let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+                for (i, chunk) in chunks.iter().enumerate() {
+                    let more = if i + 1 < chunks.len() { 1 } else { 0 };
+                    let chunk = std::str::from_utf8(chunk)?;
+                    if i == 0 {
+                        sequence.push_str(&format!("\x1b_Gf=100,a=T,t=d,q=2,C=1,m={};{}\x1b\\", more, chunk));
+                    } else {
+                        sequence.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk));
+                    }
+                }
+                sequence
+            }
+            Protocol::Iterm2 => {
+                let png = png_bytes(&resized)?;
+                format!(
+                    "\x1b]1337;File=inline=1;size={};width={}px;height={}px;preserveAspectRatio=1;doNotMoveCursor=1:{}\x07",
+                    png.len(),
+                    resized.width(),
+                    resized.height(),
+                    STANDARD.encode(&png)
+                )
+            }
+            _ => {
+                // Sixel bands are six pixels tall; rounding down keeps the image
+                // from spilling past the area and scrolling the terminal.
+                let resized = fit(img, resized.width(), resized.height() / 6 * 6);
+                sixel(&resized)
+            }
+        };
+        Ok(Encoded::Escape(sequence))
+    }
+}
+
+/// Decodes an image and rotates/flips it upright according to its EXIF orientation.
+fn load_oriented(path: &Path) -> Result<DynamicImage> {
+    let mut decoder = ImageReader::open(path)?.with_guessed_format()?.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+pub fn is_svg(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("svg"))
+}
+
+/// Parses an SVG, resolving relative image links next to the file and text
+/// with the system fonts, which are only loaded the first time.
+fn parse_svg(path: &Path) -> Result<usvg::Tree> {
+    static FONTS: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    let fontdb = FONTS.get_or_init(|| {
+        let mut fontdb = usvg::fontdb::Database::new();
+        fontdb.load_system_fonts();
+        Arc::new(fontdb)
+    });
+    let options = usvg::Options {
+        resources_dir: path.parent().map(Path::to_path_buf),
+        fontdb: fontdb.clone(),
+        ..Default::default()
+    };
+    Ok(usvg::Tree::from_data(&fs::read(path)?, &options)?)
+}
+
+/// Renders an SVG as large as fits within `width` x `height` pixels.
+fn rasterize_svg(path: &Path, width: u32, height: u32) -> Result<DynamicImage> {
+    let tree = parse_svg(path)?;
+    let size = tree.size();
+    let scale = (width.max(1) as f32 / size.width()).min(height.max(1) as f32 / size.height());
+    let scaled = size.to_int_size().scale_by(scale).ok_or_else(|| anyhow!("SVG has no visible size"))?;
+    let mut pixmap = tiny_skia::Pixmap::new(scaled.width(), scaled.height()).ok_or_else(|| anyhow!("SVG has no visible size"))?;
+    resvg::render(&tree, tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    // tiny-skia stores premultiplied alpha; `image` expects it straight.
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    let img = image::RgbaImage::from_raw(pixmap.width(), pixmap.height(), pixels).ok_or_else(|| anyhow!("Invalid SVG raster"))?;
+    Ok(DynamicImage::ImageRgba8(img))
+}
+
+fn svg_metadata(path: &Path) -> Result<Vec<(&'static str, String)>> {
+    let size = parse_svg(path)?.size();
+    Ok(vec![
+        ("Format", "SVG".to_string()),
+        ("Dimensions", format!("{} x {}", size.width().round(), size.height().round())),
+    ])
+}
+
+/// Format, dimensions and color type from the image header, followed by any
+/// EXIF tags worth showing, as label/value pairs.
+pub fn image_metadata(path: &Path) -> Result<Vec<(&'static str, String)>> {
+    if is_svg(path) {
+        return svg_metadata(path);
+    }
+    let reader = ImageReader::open(path)?.with_guessed_format()?;
+    let format = reader
+        .format()
+        .and_then(|format| format.extensions_str().first())
+        .map_or("Unknown".to_string(), |ext| ext.to_uppercase());
+    let decoder = reader.into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    let mut fields = vec![
+        ("Format", format),
+        ("Dimensions", format!("{} x {}", width, height)),
+        ("Color", format!("{:?}", decoder.color_type())),
+    ];
+    let Ok(exif) = exif::Reader::new().read_from_container(&mut BufReader::new(fs::File::open(path)?)) else {
+        return Ok(fields);
+    };
+    let text = |tag: Tag| -> Option<String> {
+        match &exif.get_field(tag, In::PRIMARY)?.value {
+            Value::Ascii(values) => {
+                let text = String::from_utf8_lossy(values.first()?).trim().to_string();
+                (!text.is_empty()).then_some(text)
+            }
+            _ => None,
+        }
+    };
+    let camera = match (text(Tag::Make), text(Tag::Model)) {
+        (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+        (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+        (make, model) => make.or(model),
+    };
+    fields.extend(camera.map(|camera| ("Camera", camera)));
+    fields.extend(text(Tag::LensModel).map(|lens| ("Lens", lens)));
+    fields.extend(text(Tag::DateTimeOriginal).or_else(|| text(Tag::DateTime)).map(|date| ("Captured", date)));
+    for (label, tag) in [
+        ("Exposure", Tag::ExposureTime),
+        ("Aperture", Tag::FNumber),
+        ("ISO", Tag::PhotographicSensitivity),
+        ("Focal length", Tag::FocalLength),
+    ] {
+        if let Some(field) = exif.get_field(tag, In::PRIMARY) {
+            fields.push((label, field.display_value().with_unit(&exif).to_string()));
+        }
+    }
+    if let Some(orientation) = exif.get_field(Tag::Orientation, In::PRIMARY).and_then(|f| f.value.get_uint(0)) {
+        let description = match orientation {
+            1 => "Normal",
+            2 => "Mirrored horizontally",
+            3 => "Rotated 180°",
+            4 => "Mirrored vertically",
+            5 => "Mirrored, rotated 90° CCW",
+            6 => "Rotated 90° CW",
+            7 => "Mirrored, rotated 90° CW",
+            8 => "Rotated 90° CCW",
+            _ => "Unknown",
+        };
+        fields.push(("Orientation", description.to_string()));
+    }
+    let coordinate = |value_tag: Tag, ref_tag: Tag, negative: &str| -> Option<f64> {
+        let Value::Rational(parts) = &exif.get_field(value_tag, In::PRIMARY)?.value else {
+            return None;
+        };
+        let degrees = parts.iter().zip([1.0, 60.0, 3600.0]).map(|(part, scale)| part.to_f64() / scale).sum::<f64>();
+        let sign = if text(ref_tag).is_some_and(|r| r.eq_ignore_ascii_case(negative)) { -1.0 } else { 1.0 };
+        Some(sign * degrees)
+    };
+    if let (Some(latitude), Some(longitude)) = (
+        coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, "S"),
+        coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, "W"),
+    ) {
+        fields.push(("GPS", format!("{:.6}, {:.6}", latitude, longitude)));
+    }
+    Ok(fields)
+}
+
+/// Scales `img` down to fit within `width` x `height`, keeping its aspect
+/// ratio. Small images are never scaled up.
+fn fit(img: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+    if img.width() <= width && img.height() <= height {
+        img.clone()
+    } else {
+        img.resize(width.max(1), height.max(1), FilterType::Triangle)
+    }
+}
+fn png_bytes(img: &DynamicImage) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// Encodes `img` as sixel using a fixed 6x6x6 color cube. Pixels that are
+/// mostly transparent are left untouched on screen.
+fn sixel(img: &DynamicImage) -> String {
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let level = |value: u8| (value as u32 * 5 + 127) / 255;
+    let indices: Vec<Option<u8>> = rgba
+        .pixels()
+        .map(|pixel| {
+            let [r, g, b, a] = pixel.0;
+            (a >= 128).then(|| (level(r) * 36 + level(g) * 6 + level(b)) as u8)
+        })
+        .collect();
+    let indices = &indices;
+
+    let mut out = format!("\x1bP0;1q\"1;1;{};{}", width, height);
+    let mut used = [false; 216];
+    for index in indices.iter().flatten() {
+        used[*index as usize] = true;
+    }
+    for (index, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+        let percent = |component: usize| component * 100 / 5;
+        out.push_str(&format!("#{};2;{};{};{}", index, percent(index / 36), percent(index / 6 % 6), percent(index % 6)));
+    }
+    for band in (0..height).step_by(6) {
+        let mut colors: Vec<u8> = (band..(band + 6).min(height))
+            .flat_map(|y| (0..width).filter_map(move |x| indices[(y * width + x) as usize]))
+            .collect();
+        colors.sort_unstable();
+        colors.dedup();
+        for (i, color) in colors.iter().enumerate() {
+            if i > 0 {
+                out.push('$');
+            }
+            out.push_str(&format!("#{}", color));
+            let mut run: Option<(char, usize)> = None;
+            for x in 0..width {
+                let mut bits = 0u8;
+                for row in 0..6 {
+                    let y = band + row;
+                    if y < height && indices[(y * width + x) as usize] == Some(*color) {
+                        bits |= 1 << row;
+                    }
+                }
+                let symbol = (b'?' + bits) as char;
+                run = match run {
+                    Some((previous, count)) if previous == symbol => Some((symbol, count + 1)),
+                    Some((previous, count)) => {
+                        push_sixel_run(&mut out, previous, count);
+                        Some((symbol, 1))
+                    }
+                    None => Some((symbol, 1)),
+                };
+            }
+            if let Some((symbol, count)) = run {
+                push_sixel_run(&mut out, symbol, count);
+            }
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
+fn push_sixel_run(out: &mut String, symbol: char, count: usize) {
+    if count > 3 {
+        out.push_str(&format!("!{}{}", count, symbol));
+    } else {
+        out.extend(std::iter::repeat_n(symbol, count));
+    }
+}
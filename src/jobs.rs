@@ -0,0 +1,111 @@
+use anyhow::Result;
+use std::{
+    io::{self, Read},
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+enum JobEvent {
+    Progress { id: usize, done: u64, total: u64 },
+    Finished { id: usize, result: Result<String, String> },
+}
+
+/// Handle given to a running job to report how far along it is.
+pub struct Progress {
+    id: usize,done: u64,total: u64,reported: u64,sender: Sender<JobEvent>,
+}
+impl Progress {
+    pub fn set_total(&mut self, total: u64) {
+        self.total = total;
+        self.send();
+    }
+    pub fn advance(&mut self, amount: u64) {
+        const REPORT_EVERY_BYTES: u64 = 256 * 1024;
+        self.done += amount;
+        if self.done - self.reported >= REPORT_EVERY_BYTES || self.done >= self.total {
+            self.send();
+        }
+    }
+    /// Moves progress forward to `done`, for work measured by position
+    /// rather than by the amount handed over.
+    pub fn set_done(&mut self, done: u64) {
+        self.advance(done.saturating_sub(self.done));
+    }
+    fn send(&mut self) {
+        self.reported = self.done;
+        let _ = self.sender.send(JobEvent::Progress { id: self.id, done: self.done, total: self.total });
+    }
+}
+
+/// Wraps a reader and reports every chunk read through `progress`.
+pub struct ProgressReader<'a, R> {
+    pub inner: R,pub progress: &'a mut Progress,
+}
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.progress.advance(n as u64);
+        Ok(n)
+    }
+}
+
+pub struct Job {
+    pub id: usize,pub label: String,pub dir: PathBuf,pub done: u64,pub total: u64,
+}
+impl Job {
+    pub fn percent(&self) -> u64 {
+        (self.done * 100).checked_div(self.total).unwrap_or(0).min(100)
+    }
+}
+
+/// A job that ended since the last poll, with its success message or error.
+pub struct FinishedJob {
+    pub label: String,pub dir: PathBuf,pub result: Result<String, String>,
+}
+
+pub struct JobManager {
+    pub jobs: Vec<Job>,next_id: usize,sender: Sender<JobEvent>,receiver: Receiver<JobEvent>,
+}
+impl JobManager {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { jobs: Vec::new(), next_id: 0, sender, receiver }
+    }
+    /// Runs `work` on a background thread. `dir` is the directory the job
+    /// writes into, so the listing can be refreshed when it finishes.
+    pub fn spawn<F>(&mut self, label: String, dir: PathBuf, work: F)
+    where
+        F: FnOnce(&mut Progress) -> Result<String> + Send + 'static,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.jobs.push(Job { id, label, dir, done: 0, total: 0 });
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let mut progress = Progress { id, done: 0, total: 0, reported: 0, sender: sender.clone() };
+            let result = work(&mut progress).map_err(|err| err.to_string());
+            let _ = sender.send(JobEvent::Finished { id, result });
+        });
+    }
+    pub fn poll(&mut self) -> Vec<FinishedJob> {
+        let mut finished = Vec::new();
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                JobEvent::Progress { id, done, total } => {
+                    if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+                        job.done = done;
+                        job.total = total;
+                    }
+                }
+                JobEvent::Finished { id, result } => {
+                    if let Some(index) = self.jobs.iter().position(|job| job.id == id) {
+                        let job = self.jobs.remove(index);
+                        finished.push(FinishedJob { label: job.label, dir: job.dir, result });
+                    }
+                }
+            }
+        }
+        finished
+    }
+}
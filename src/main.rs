@@ -1,47 +1,165 @@
-use anyhow::{Context, Result};
+mod archive;
+mod cli;
+mod config;
+mod database;
+mod document;
+mod encoding;
+mod executable;
+mod filetype;
+mod follow;
+mod image_preview;
+mod jobs;
+mod markdown;
+mod media;
+mod opener;
+mod pager;
+mod panics;
+mod previewer;
+mod shell;
+mod structured;
+
+use anyhow::{bail, Context, Result};
+use archive::{ArchiveBrowser, ArchiveKind};
+use cli::{ChooseKind, SortMode};
+use config::Config;
+use database::DatabaseView;
+use filetype::{Category, FileType, FileTypeCache};
+use follow::{FollowLine, Follower};
+use image_preview::ImagePreview;
+use jobs::JobManager;
+use media::MediaCache;
+use opener::{Opener, OpenerRegistry};
+use pager::{Pager, PagerPrompt};
+use previewer::{ExternalPreview, PreviewerRegistry};
+use shell::{ShellOutput, ShellRunner};
+use structured::{DataFormat, TableData};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},execute,terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use nerd_font_symbols::md;
 use ratatui::{
-    prelude::*,widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    prelude::*,widgets::{Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table},
 };
 use std::{
-    env,fs,io::{self, Read},path::{Path, PathBuf},time::Instant,
+    borrow::Cow,cmp::Reverse,env,fs,io::{self, IsTerminal},ops::Range,path::{Path, PathBuf},process::ExitCode,time::{Instant, SystemTime},
 };
-use trash;
-use viuer;
-use open;
 
 const ACTIONS: &[(&str, &str)] = &[
-    ("Cut", "X"),("Copy", "C"),("Paste", "V"),("Delete", "D"),("Rename", "R"),("Create", "N"),("Create Directory", "+"),("Move", "M"),("Open", "O"),("Toggle Hidden", "Shift+H"),
+    ("Cut", "X"),("Copy", "C"),("Paste", "V"),("Delete", "D"),("Rename", "R"),("Create", "N"),("Create Directory", "+"),("Move", "M"),("Open", "O"),("Toggle Hidden", "Shift+H"),("Extract Here", "E"),("Extract to Folder", "Shift+E"),("Compress", "Z"),("Edit", "I"),("Open With", "Shift+O"),("Shell Command", "!"),("Open Shell Here", "Shift+S"),
+];
+/// Actions that change files or run arbitrary programs, greyed out in
+/// read-only mode.
+const MUTATING_ACTIONS: &[&str] = &[
+    "Cut","Paste","Delete","Rename","Create","Create Directory","Move","Extract Here","Extract to Folder","Compress","Edit","Shell Command","Open Shell Here",
 ];
+/// Data files and Markdown larger than this are not parsed, in the pane or
+/// the full-screen viewer, and fall back to plain text.
+const MAX_STRUCTURED_PREVIEW_BYTES: u64 = 10 * 1024 * 1024;
+const COMPRESS_FORMATS: &[ArchiveKind] = &[ArchiveKind::Zip, ArchiveKind::TarGz, ArchiveKind::TarZst];
 const VIM_KEY_HINTS: &[(&str, &str, &str)] = &[
     ("J", "Down Arrow", "Move down in file list"),("K", "Up Arrow", "Move up in file list"),("H", "Left Arrow", "Unfocus actions panel / Go up directory"),("L", "Right Arrow", "Focus actions panel / Open selected"),("Q", "Quit", "Quit the application"),
 ];
 
 #[derive(PartialEq)]
 enum AppMode {
-    Normal,ConfirmDelete,Editing,Create,Rename,Filter,CreateDirectory,Move,
+    Normal,ConfirmDelete,Editing,Create,Rename,Filter,CreateDirectory,Move,Compress,Database,Highlight,Pager,OpenWith,Shell,
 }
 #[derive(PartialEq)]
 enum PanelFocus {
     Files,Actions,
 }
+#[derive(Clone)]
+enum Clipboard {
+    Path(PathBuf),ArchiveEntry { archive: PathBuf, kind: ArchiveKind, entry: String },
+}
+/// The terminal the TUI draws on, see [`terminal_output`].
+type Tui = Terminal<CrosstermBackend<Box<dyn io::Write>>>;
+/// How the user left Karu.
+#[derive(PartialEq)]
+enum Quit {
+    /// `q`: shell wrappers should cd to the last directory.
+    ChangeDirectory,
+    /// `Shift+Q`: leave the shell where it was.
+    KeepDirectory,
+    /// Paths picked in `--choose-files`/`--choose-dir` mode.
+    Chose(Vec<PathBuf>),
+}
+/// A command to run in this terminal while the TUI is suspended.
+struct InteractiveCommand {
+    command: String,dir: PathBuf,after_exit: AfterExit,
+}
+#[derive(PartialEq)]
+enum AfterExit {
+    /// Show an error popup if the program failed.
+    ReportFailure,
+    /// Keep the output on screen until Enter is pressed.
+    WaitForEnter,
+    /// A shell's exit status is just that of its last command.
+    Ignore,
+}
+/// Rendered preview kept between frames so expensive previews (archive
+/// listings, decompressed members, CSV tables) are only built once per
+/// selection.
+struct CachedPreview<T = Text<'static>> {
+    key: PathBuf,modified: Option<SystemTime>,title: String,content: T,
+}
 struct App {
-    path: PathBuf,files: Vec<String>,selected: usize,mode: AppMode,address_input: String,cursor_position: usize,create_input: String,rename_input: String,clipboard: Option<PathBuf>,is_cut: bool,show_hidden: bool,filter_input: String,create_directory_input: String,move_input: String,selected_action: usize,panel_focus: PanelFocus,action_list_state: ListState,error_message: Option<String>,delayed_preview_path: Option<PathBuf>,last_highlight_time: Instant,notification: Option<String>,notification_time: Option<Instant>,
+    path: PathBuf,files: Vec<String>,selected: usize,mode: AppMode,address_input: String,cursor_position: usize,create_input: String,rename_input: String,clipboard: Option<Clipboard>,is_cut: bool,show_hidden: bool,filter_input: String,create_directory_input: String,move_input: String,selected_action: usize,panel_focus: PanelFocus,action_list_state: ListState,error_message: Option<String>,delayed_preview_path: Option<PathBuf>,last_highlight_time: Instant,notification: Option<String>,notification_time: Option<Instant>,archive: Option<ArchiveBrowser>,preview_cache: Option<CachedPreview>,table_cache: Option<CachedPreview<Result<TableData>>>,marked: Vec<PathBuf>,jobs: JobManager,compress_input: String,compress_format: usize,image: ImagePreview,fold_depth: Option<usize>,tree_depth: usize,raw_preview: bool,previewers: PreviewerRegistry,file_types: FileTypeCache,database: Option<DatabaseView>,media: MediaCache,follow: Option<Follower>,highlight_input: String,pager: Option<Pager>,interactive: Option<InteractiveCommand>,openers: OpenerRegistry,open_with: Vec<Opener>,open_with_selected: usize,open_with_input: String,shell: ShellRunner,shell_input: String,shell_in_terminal: bool,shell_output: Option<ShellOutput>,shell_scroll: u16,chooser: Option<ChooseKind>,chosen: Option<Vec<PathBuf>>,sort: SortMode,read_only: bool,
 }
 impl App {
     fn new(path: PathBuf) -> Result<Self> {
         let normalized_path = Self::normalize_path(&path)?;
-        let files = Self::get_files(&normalized_path, true)?;
+        let files = Self::get_files(&normalized_path, true, SortMode::Name)?;
         let address_input = normalized_path
             .to_str()
             .context("Invalid path")?
             .to_string();
         let cursor_position = address_input.len();
         Ok(Self {
-            path: normalized_path,files,selected: 0,mode: AppMode::Normal,address_input,cursor_position,create_input: String::new(),rename_input: String::new(),clipboard: None,is_cut: false,show_hidden: true,filter_input: String::new(),create_directory_input: String::new(),move_input: String::new(),selected_action: 0,panel_focus: PanelFocus::Files,action_list_state: ListState::default(),error_message: None,delayed_preview_path: None,last_highlight_time: Instant::now(),notification: None,notification_time: None, })
+            path: normalized_path,files,selected: 0,mode: AppMode::Normal,address_input,cursor_position,create_input: String::new(),rename_input: String::new(),clipboard: None,is_cut: false,show_hidden: true,filter_input: String::new(),create_directory_input: String::new(),move_input: String::new(),selected_action: 0,panel_focus: PanelFocus::Files,action_list_state: ListState::default(),error_message: None,delayed_preview_path: None,last_highlight_time: Instant::now(),notification: None,notification_time: None,archive: None,preview_cache: None,table_cache: None,marked: Vec::new(),jobs: JobManager::new(),compress_input: String::new(),compress_format: 0,image: ImagePreview::new(),fold_depth: None,tree_depth: 0,raw_preview: false,previewers: PreviewerRegistry::new(&[])?,file_types: FileTypeCache::default(),database: None,media: MediaCache::default(),follow: None,highlight_input: String::new(),pager: None,interactive: None,openers: OpenerRegistry::new(&[])?,open_with: Vec::new(),open_with_selected: 0,open_with_input: String::new(),shell: ShellRunner::new(),shell_input: String::new(),shell_in_terminal: false,shell_output: None,shell_scroll: 0,chooser: None,chosen: None,sort: SortMode::Name,read_only: false, })
+    }
+    /// Builds the app in the state asked for on the command line.
+    fn from_args(args: &cli::Args) -> Result<Self> {
+        let start = match &args.path {
+            Some(path) => {
+                let path = Self::normalize_path(path)?;
+                fs::canonicalize(&path).with_context(|| format!("Cannot open '{}'", path.display()))?
+            }
+            None => env::current_dir()?,
+        };
+        let (dir, file_name) = match (start.is_dir(), start.parent(), start.file_name()) {
+            (false, Some(parent), Some(name)) => (parent.to_path_buf(), Some(name.to_string_lossy().to_string())),
+            _ => (start, None),
+        };
+        let mut app = Self::new(dir)?;
+        app.show_hidden = args.show_hidden.unwrap_or(true);
+        app.sort = args.sort.unwrap_or(SortMode::Name);
+        app.read_only = args.read_only;
+        app.chooser = args.choose.as_ref().map(|chooser| chooser.kind);
+        app.refresh_files()?;
+        if let Some(name) = file_name {
+            app.selected = app.files.iter().position(|file| *file == name).unwrap_or(0);
+        }
+        // Unlike the default config, one named on the command line must exist.
+        if let Some(path) = &args.config {
+            if !path.is_file() {
+                bail!("Config file '{}' not found", path.display());
+            }
+        }
+        if let Some(config_path) = args.config.clone().or_else(config::default_path) {
+            if let Err(err) = app.load_config(&config_path) {
+                app.error_message = Some(format!("{:#}", err));
+            }
+        }
+        Ok(app)
+    }
+    /// Applies settings from the config file at `path`.
+    fn load_config(&mut self, path: &Path) -> Result<()> {
+        let config = Config::load(path)?;
+        self.previewers = PreviewerRegistry::new(&config.previewers)?;
+        self.openers = OpenerRegistry::new(&config.openers)?;
+        // The config can turn read-only mode on, but never off again.
+        self.read_only |= config.read_only;
+        Ok(())
     }
     fn normalize_path(path: &Path) -> Result<PathBuf> {
         if path.starts_with("~") {
@@ -54,7 +172,7 @@ impl App {
             Ok(path.to_path_buf())
         }
     }
-    fn get_files(path: &Path, show_hidden: bool) -> Result<Vec<String>> {
+    fn get_files(path: &Path, show_hidden: bool, sort: SortMode) -> Result<Vec<String>> {
         let mut all_entries: Vec<PathBuf> = fs::read_dir(path)?
             .filter_map(|res| res.ok().map(|e| e.path()))
             .collect();
@@ -64,6 +182,13 @@ impl App {
                 .to_ascii_lowercase()
                 .cmp(&b.file_name().unwrap_or_default().to_ascii_lowercase())
         });
+        // Stable sorts, so entries that compare equal stay in name order.
+        match sort {
+            SortMode::Name => {}
+            SortMode::Size => all_entries.sort_by_cached_key(|entry| Reverse(fs::metadata(entry).map_or(0, |m| m.len()))),
+            SortMode::Modified => all_entries.sort_by_cached_key(|entry| Reverse(fs::metadata(entry).and_then(|m| m.modified()).ok())),
+            SortMode::Extension => all_entries.sort_by_cached_key(|entry| entry.extension().map(|ext| ext.to_ascii_lowercase())),
+        }
         let mut hidden_dirs = Vec::new();
         let mut normal_dirs = Vec::new();
         let mut hidden_files = Vec::new();
@@ -119,25 +244,339 @@ impl App {
             self.go_up_directory()?;
             return Ok(())
         }
+        if let Some(browser) = &mut self.archive {
+            let entry_path = browser.entry_path(selected_file);
+            if !browser.is_dir(&entry_path) {
+                bail!("'{}' is inside an archive, copy it out to open it", selected_file);
+            }
+            browser.prefix = entry_path;
+            self.refresh_files()?;
+            self.selected = 0;
+            return Ok(());
+        }
         let new_path = self.path.join(selected_file);
         let normalized_path = Self::normalize_path(&new_path)?;
         if normalized_path.is_dir() {
             self.path = normalized_path;
-            self.files = Self::get_files(&self.path, self.show_hidden)?;
+            self.files = Self::get_files(&self.path, self.show_hidden, self.sort)?;
             self.selected = 0;
+        } else if let Some(kind) = archive_kind(&normalized_path, self.file_types.update(&normalized_path)) {
+            self.archive = Some(ArchiveBrowser::open(normalized_path, kind)?);
+            self.refresh_files()?;
+            self.selected = 0;
+        } else if self.file_types.update(&normalized_path).category == Category::Database {
+            self.database = Some(DatabaseView::open(normalized_path)?);
+            self.mode = AppMode::Database;
+        } else {
+            self.open_path(&normalized_path)?;
+        }
+        Ok(())
+    }
+    /// Re-reads the listing for the current location, which is either
+    /// `self.path` or the current directory of an open archive.
+    fn refresh_files(&mut self) -> Result<()> {
+        self.files = match &self.archive {
+            Some(browser) => {
+                let mut files = vec!["..".to_string()];
+                files.extend(browser.children(&browser.prefix, self.show_hidden));
+                files
+            }
+            None => Self::get_files(&self.path, self.show_hidden, self.sort)?,
+        };
+        Ok(())
+    }
+    fn entry_is_dir(&self, name: &str) -> bool {
+        match &self.archive {
+            Some(browser) => name == ".." || browser.is_dir(&browser.entry_path(name)),
+            None => self.path.join(name).is_dir(),
+        }
+    }
+    /// Detected type of a listed entry. Archive members are typed by name
+    /// since reading each of them would mean decompressing the archive.
+    fn entry_type(&self, name: &str) -> FileType {
+        match &self.archive {
+            Some(_) => FileType::guess(Path::new(name), self.entry_is_dir(name)),
+            None => self.file_types.get(&self.path.join(name)).unwrap_or(FileType::guess(Path::new(name), self.entry_is_dir(name))),
+        }
+    }
+    /// Sniffs the type of the listed files in `rows` (the part of the list
+    /// on screen) and of the selected one, if their contents changed. The
+    /// rest keep the type guessed from their name until scrolled into view.
+    fn update_file_types(&mut self, rows: Range<usize>) {
+        if self.archive.is_some() {
+            return;
+        }
+        let rows = rows.start.min(self.files.len())..rows.end.min(self.files.len());
+        for name in self.files[rows].iter().chain(self.files.get(self.selected)) {
+            self.file_types.update(&self.path.join(name));
+        }
+    }
+    fn entry_size(&self, name: &str) -> Option<u64> {
+        match &self.archive {
+            Some(browser) => browser.entry(&browser.entry_path(name)).map(|entry| entry.size),
+            None => fs::metadata(self.path.join(name)).ok().map(|metadata| metadata.len()),
+        }
+    }
+    /// Refuses `action` (e.g. "Deleting") in read-only mode.
+    fn ensure_not_read_only(&self, action: &str) -> Result<()> {
+        if self.read_only {
+            bail!("{} is disabled in read-only mode", action);
+        }
+        Ok(())
+    }
+    fn ensure_writable(&self, action: &str) -> Result<()> {
+        self.ensure_not_read_only(action)?;
+        if self.archive.is_some() {
+            bail!("Archives are read-only, copy entries out and paste them elsewhere");
+        }
+        Ok(())
+    }
+    /// Whether a popup is drawn over the panels this frame.
+    fn has_popup(&self) -> bool {
+        self.error_message.is_some() || self.shell_output.is_some() || !matches!(self.mode, AppMode::Normal | AppMode::Editing)
+    }
+    /// Queues `$VISUAL` or `$EDITOR` on the highlighted file, to run in
+    /// this terminal once the TUI is suspended.
+    fn edit_selected(&mut self) -> Result<()> {
+        self.ensure_not_read_only("Editing files")?;
+        let default = if cfg!(windows) { "notepad" } else { "vi" };
+        let editor = ["VISUAL", "EDITOR"]
+            .iter()
+            .find_map(|name| env::var(name).ok().filter(|value| !value.trim().is_empty()))
+            .unwrap_or_else(|| default.to_string());
+        self.run_on_selected(&editor)
+    }
+    fn page_selected(&mut self) -> Result<()> {
+        // Pagers can run commands (`!` and `v` in less), so they're as
+        // powerful as the editor; the built-in viewer (V) still works.
+        self.ensure_not_read_only("Paging in $PAGER")?;
+        let default = if cfg!(windows) { "more" } else { "less" };
+        let pager = env::var("PAGER").ok().filter(|value| !value.trim().is_empty()).unwrap_or_else(|| default.to_string());
+        self.run_on_selected(&pager)
+    }
+    fn run_on_selected(&mut self, program: &str) -> Result<()> {
+        let selected_file = &self.files[self.selected];
+        if self.archive.is_some() {
+            bail!("'{}' is inside an archive, copy it out to open it", selected_file);
+        }
+        let path = self.path.join(selected_file);
+        if path.is_dir() {
+            bail!("Select a file to open");
+        }
+        // The variable may hold arguments too (`code --wait`), so the shell splits it.
+        let command = format!("{} {}", program, previewer::shell_quote(&path.to_string_lossy()));
+        self.interactive = Some(InteractiveCommand { command, dir: self.path.clone(), after_exit: AfterExit::ReportFailure });
+        Ok(())
+    }
+    /// Reloads the listing after an external program ran, keeping the same
+    /// entry highlighted if it still exists.
+    fn refresh_after_command(&mut self) -> Result<()> {
+        let selected = self.files.get(self.selected).cloned();
+        self.refresh_files()?;
+        self.selected = selected
+            .and_then(|name| self.files.iter().position(|file| *file == name))
+            .unwrap_or(self.selected)
+            .min(self.files.len().saturating_sub(1));
+        self.preview_cache = None;
+        Ok(())
+    }
+    /// Starts following the highlighted file like `tail -F`, or stops.
+    fn toggle_follow(&mut self) -> Result<()> {
+        if self.follow.take().is_some() {
+            return Ok(());
+        }
+        if self.archive.is_some() {
+            bail!("Files inside an archive cannot be followed");
+        }
+        let path = self.path.join(&self.files[self.selected]);
+        if path.is_dir() {
+            bail!("Only files can be followed");
+        }
+        self.follow = Some(Follower::open(&path)?);
+        Ok(())
+    }
+    fn toggle_follow_pause(&mut self) -> Result<()> {
+        let Some(follower) = &mut self.follow else {
+            bail!("Not following a file; press Shift+F to start");
+        };
+        follower.paused = !follower.paused;
+        Ok(())
+    }
+    fn begin_highlight(&mut self) -> Result<()> {
+        let Some(follower) = &self.follow else {
+            bail!("Not following a file; press Shift+F to start");
+        };
+        self.highlight_input = follower.highlight.as_ref().map(|regex| regex.to_string()).unwrap_or_default();
+        self.mode = AppMode::Highlight;
+        Ok(())
+    }
+    fn apply_highlight(&mut self) -> Result<()> {
+        let highlight = if self.highlight_input.is_empty() {
+            None
         } else {
-            open::that(&normalized_path)?;
+            Some(regex::Regex::new(&self.highlight_input).context("Invalid highlight pattern")?)
+        };
+        if let Some(follower) = &mut self.follow {
+            follower.highlight = highlight;
+        }
+        self.mode = AppMode::Normal;
+        Ok(())
+    }
+    /// Opens the highlighted file in the full-screen viewer. The list is
+    /// left untouched, so closing it returns to the same place.
+    fn open_pager(&mut self) -> Result<()> {
+        let name = self.files[self.selected].clone();
+        let (path, title, text) = self.pager_content(&name)?;
+        self.pager = Some(Pager::new(path, title, text.lines));
+        self.mode = AppMode::Pager;
+        Ok(())
+    }
+    /// Rebuilds the viewer's lines after a display option changed, keeping
+    /// the cursor, search and wrap setting.
+    fn reload_pager(&mut self) -> Result<()> {
+        let Some(old) = self.pager.take() else {
+            return Ok(());
+        };
+        let name = old.path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let (path, title, text) = self.pager_content(&name)?;
+        let mut pager = Pager::new(path, title, text.lines);
+        pager.height = old.height;
+        pager.wrap = old.wrap;
+        pager.search = old.search;
+        pager.goto_line(old.cursor + 1);
+        self.pager = Some(pager);
+        Ok(())
+    }
+    fn pager_content(&mut self, name: &str) -> Result<(PathBuf, String, Text<'static>)> {
+        const MAX_PAGER_BYTES: u64 = 16 * 1024 * 1024;
+        if name == ".." {
+            bail!("Select a file to view");
+        }
+        if let Some(browser) = &self.archive {
+            let entry_path = browser.entry_path(name);
+            if browser.is_dir(&entry_path) {
+                bail!("Select a file to view");
+            }
+            let data = archive::read_entry(&browser.archive_path, browser.kind, &entry_path, MAX_PAGER_BYTES)?;
+            let text = if filetype::detect_bytes(&data, Path::new(&entry_path)).is_text() {
+                plain_text(&encoding::decode(&data, data.len() as u64 >= MAX_PAGER_BYTES).text)
+            } else {
+                Text::from(pager::hex_lines(&data))
+            };
+            return Ok((browser.archive_path.join(&entry_path), name.to_string(), text));
+        }
+        let path = self.path.join(name);
+        if path.is_dir() {
+            bail!("Select a file to view");
+        }
+        let file_type = self.file_types.update(&path);
+        let width = crossterm::terminal::size().map_or(80, |(width, _)| width as usize).saturating_sub(10);
+        let (title, text) = full_preview(&path, file_type, self.raw_preview, self.fold_depth, &mut self.tree_depth, width);
+        // Preview titles read "Preview (details)"; the viewer names the file instead.
+        let details = title.strip_prefix("Preview").unwrap_or(&title).trim();
+        Ok((path, format!("{} {}", name, details), text))
+    }
+    fn begin_input(&mut self, mode: AppMode) -> Result<()> {
+        let action = match mode {
+            AppMode::Create => "Creating files",
+            AppMode::CreateDirectory => "Creating directories",
+            AppMode::Rename => "Renaming",
+            _ => "Moving",
+        };
+        self.ensure_writable(action)?;
+        self.mode = mode;
+        Ok(())
+    }
+    fn toggle_mark(&mut self) -> Result<()> {
+        if self.archive.is_some() {
+            bail!("Entries inside an archive cannot be marked");
+        }
+        let selected_file = &self.files[self.selected];
+        if selected_file != ".." {
+            let path = self.path.join(selected_file);
+            match self.marked.iter().position(|marked| *marked == path) {
+                Some(index) => {
+                    self.marked.remove(index);
+                }
+                None => self.marked.push(path),
+            }
+        }
+        self.select_next();
+        Ok(())
+    }
+    /// Marked entries if there are any, otherwise the highlighted entry.
+    fn selection(&self) -> Vec<PathBuf> {
+        if !self.marked.is_empty() {
+            return self.marked.clone();
+        }
+        match self.files.get(self.selected) {
+            Some(selected_file) if selected_file != ".." => vec![self.path.join(selected_file)],
+            _ => Vec::new(),
+        }
+    }
+    fn extract_selected(&mut self, into_folder: bool) -> Result<()> {
+        self.ensure_writable("Extracting")?;
+        let mut archives = Vec::new();
+        for path in self.selection() {
+            let name = path.file_name().context("Failed to get file name")?.to_string_lossy().to_string();
+            let kind = ArchiveKind::from_path(&path).with_context(|| format!("'{}' is not a supported archive", name))?;
+            archives.push((path, name, kind));
+        }
+        if archives.is_empty() {
+            bail!("Nothing selected to extract");
+        }
+        for (path, name, kind) in archives {
+            let dest = if into_folder {
+                unique_path(&self.path.join(ArchiveKind::stem(&name)))
+            } else {
+                self.path.clone()
+            };
+            self.jobs.spawn(format!("Extracting '{}'", name), self.path.clone(), move |progress| {
+                let count = archive::extract_all(&path, kind, &dest, progress)?;
+                Ok(format!("Extracted {} files from '{}'", count, name))
+            });
         }
+        self.marked.clear();
+        Ok(())
+    }
+    fn begin_compress(&mut self) -> Result<()> {
+        self.ensure_writable("Compressing")?;
+        let sources = self.selection();
+        self.compress_input = match sources.as_slice() {
+            [] => bail!("Nothing selected to compress"),
+            [single] => single.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            _ => self.path.file_name().map_or("archive".to_string(), |name| name.to_string_lossy().to_string()),
+        };
+        self.mode = AppMode::Compress;
+        Ok(())
+    }
+    fn compress_selected(&mut self) -> Result<()> {
+        let kind = COMPRESS_FORMATS[self.compress_format];
+        let name = format!("{}.{}", self.compress_input, kind.name());
+        let dest = self.path.join(&name);
+        if dest.exists() {
+            bail!("'{}' already exists", name);
+        }
+        let sources = self.selection();
+        self.jobs.spawn(format!("Compressing '{}'", name), self.path.clone(), move |progress| {
+            let count = archive::compress(&sources, &dest, kind, progress)?;
+            Ok(format!("Compressed {} files into '{}'", count, name))
+        });
+        self.marked.clear();
+        self.compress_input.clear();
+        self.mode = AppMode::Normal;
         Ok(())
     }
-    fn delete_selected(&mut self) {
+    fn delete_selected(&mut self) -> Result<()> {
+        self.ensure_writable("Deleting")?;
         self.mode = AppMode::ConfirmDelete;
+        Ok(())
     }
     fn confirm_delete(&mut self) -> Result<()> {
         let selected_file = self.files[self.selected].clone();
         let path = self.path.join(&selected_file);
         trash::delete(path)?;
-        self.files = Self::get_files(&self.path, self.show_hidden)?;
+        self.files = Self::get_files(&self.path, self.show_hidden, self.sort)?;
         self.selected = 0;
         self.mode = AppMode::Normal;
         self.notification = Some(format!("Deleted '{}'", selected_file));
@@ -149,22 +588,38 @@ impl App {
     }
     fn copy_selected(&mut self) {
         let selected_file = self.files[self.selected].clone();
-        let path = self.path.join(&selected_file);
-        self.clipboard = Some(path);
+        self.clipboard = Some(match &self.archive {
+            Some(browser) => Clipboard::ArchiveEntry {
+                archive: browser.archive_path.clone(),kind: browser.kind,entry: browser.entry_path(&selected_file),
+            },
+            None => Clipboard::Path(self.path.join(&selected_file)),
+        });
+        self.is_cut = false;
         self.notification = Some(format!("Copied '{}'", selected_file));
         self.notification_time = Some(Instant::now());
     }
-    fn cut_selected(&mut self) {
+    fn cut_selected(&mut self) -> Result<()> {
+        self.ensure_writable("Cutting")?;
         let selected_file = self.files[self.selected].clone();
         let path = self.path.join(&selected_file);
-        self.clipboard = Some(path);
+        self.clipboard = Some(Clipboard::Path(path));
         self.is_cut = true;
         self.mode = AppMode::Normal;
         self.notification = Some(format!("Cut '{}'", selected_file));
         self.notification_time = Some(Instant::now());
+        Ok(())
     }
     fn paste(&mut self) -> Result<()> {
-        if let Some(from) = self.clipboard.clone() {
+        self.ensure_writable("Pasting")?;
+        if let Some(Clipboard::ArchiveEntry { archive, kind, entry }) = &self.clipboard {
+            archive::extract_entry(archive, *kind, entry, &self.path)?;
+            let name = entry.rsplit('/').next().unwrap_or(entry).to_string();
+            self.files = Self::get_files(&self.path, self.show_hidden, self.sort)?;
+            self.notification = Some(format!("Pasted '{}'", name));
+            self.notification_time = Some(Instant::now());
+            return Ok(());
+        }
+        if let Some(Clipboard::Path(from)) = self.clipboard.clone() {
             let file_name = from.file_name().context("Failed to get file name")?;
             let to = self
                 .path
@@ -189,7 +644,7 @@ impl App {
                 self.is_cut = false;
                 self.clipboard = None;
             }
-            self.files = Self::get_files(&self.path, self.show_hidden)?;
+            self.files = Self::get_files(&self.path, self.show_hidden, self.sort)?;
             self.notification = Some(format!("Pasted '{}'", file_name.to_string_lossy()));
             self.notification_time = Some(Instant::now());
         }
@@ -198,27 +653,219 @@ impl App {
 
     fn open_file(&mut self) -> Result<()> {
         let selected_file = &self.files[self.selected];
+        if self.archive.is_some() {
+            bail!("'{}' is inside an archive, copy it out to open it", selected_file);
+        }
         let path = self.path.join(selected_file);
         if !path.is_dir() {
-            open::that(&path)?;
+            self.open_path(&path)?;
+        }
+        Ok(())
+    }
+    /// Opens a file with its default opener from the config, or the
+    /// system's default application when no rule matches.
+    fn open_path(&mut self, path: &Path) -> Result<()> {
+        let mime = self.file_types.update(path).mime;
+        match self.openers.default_for(path, mime) {
+            Some(opener) => self.run_opener(&opener, path),
+            None => Ok(open::that(path)?),
+        }
+    }
+    fn run_opener(&mut self, opener: &Opener, path: &Path) -> Result<()> {
+        let command = opener.command_for(path);
+        if opener.terminal {
+            self.interactive = Some(InteractiveCommand { command, dir: self.path.clone(), after_exit: AfterExit::ReportFailure });
+        } else {
+            opener::spawn_detached(&command)?;
+            self.notification = Some(format!("Opened with {}", opener.name));
+            self.notification_time = Some(Instant::now());
+        }
+        Ok(())
+    }
+    /// Lists every opener configured for the highlighted file, plus the
+    /// system default and a free-form command.
+    fn begin_open_with(&mut self) -> Result<()> {
+        let selected_file = &self.files[self.selected];
+        if self.archive.is_some() {
+            bail!("'{}' is inside an archive, copy it out to open it", selected_file);
+        }
+        let path = self.path.join(selected_file);
+        if path.is_dir() {
+            bail!("Select a file to open");
+        }
+        let mime = self.file_types.update(&path).mime;
+        self.open_with = self.openers.matching(&path, mime);
+        self.open_with_selected = 0;
+        self.open_with_input.clear();
+        self.mode = AppMode::OpenWith;
+        Ok(())
+    }
+    /// Runs the typed command if there is one, otherwise the chosen entry.
+    /// A typed command ending in `&` is detached like a GUI opener.
+    fn confirm_open_with(&mut self) -> Result<()> {
+        let path = self.path.join(&self.files[self.selected]);
+        let input = self.open_with_input.trim();
+        self.mode = AppMode::Normal;
+        if !input.is_empty() {
+            self.ensure_not_read_only("Running typed commands")?;
+            let (command, detached) = match input.strip_suffix('&') {
+                Some(command) => (command.trim_end(), true),
+                None => (input, false),
+            };
+            let opener = Opener { name: command.to_string(), command: command.to_string(), terminal: !detached };
+            return self.run_opener(&opener, &path);
+        }
+        match self.open_with.get(self.open_with_selected).cloned() {
+            Some(opener) => self.run_opener(&opener, &path),
+            None => Ok(open::that(&path)?),
+        }
+    }
+    /// Suspends Karu and starts `$SHELL` in the current directory,
+    /// returning when it exits.
+    fn spawn_shell(&mut self) -> Result<()> {
+        self.ensure_not_read_only("Opening a shell")?;
+        if self.archive.is_some() {
+            bail!("Shell commands run in a directory, leave the archive first");
+        }
+        let (variable, default) = if cfg!(windows) { ("COMSPEC", "cmd") } else { ("SHELL", "sh") };
+        let shell = env::var(variable).ok().filter(|value| !value.trim().is_empty()).unwrap_or_else(|| default.to_string());
+        let command = previewer::shell_quote(&shell);
+        self.interactive = Some(InteractiveCommand { command, dir: self.path.clone(), after_exit: AfterExit::Ignore });
+        Ok(())
+    }
+    /// Whether Enter picks the highlighted entry rather than opening it:
+    /// choosing files, with a file highlighted.
+    fn chooses_on_enter(&self) -> bool {
+        self.chooser == Some(ChooseKind::Files)
+            && self.archive.is_none()
+            && self.files.get(self.selected).is_some_and(|name| !self.path.join(name).is_dir())
+    }
+    /// Picks the marked entries, else the highlighted one (or the current
+    /// directory on `..` when choosing a directory), ending the session.
+    fn choose(&mut self) -> Result<()> {
+        let Some(kind) = self.chooser else {
+            return Ok(());
+        };
+        if self.archive.is_some() {
+            bail!("Entries inside an archive can't be chosen, copy them out first");
+        }
+        let mut paths = self.selection();
+        if paths.is_empty() && kind == ChooseKind::Dir {
+            paths.push(self.path.clone());
+        }
+        let name = |path: &Path| path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        match kind {
+            ChooseKind::Files => {
+                if let Some(dir) = paths.iter().find(|path| path.is_dir()) {
+                    bail!("'{}' is a directory, choose files", name(dir));
+                }
+            }
+            ChooseKind::Dir => {
+                if let Some(file) = paths.iter().find(|path| !path.is_dir()) {
+                    bail!("'{}' is not a directory", name(file));
+                }
+            }
+        }
+        if paths.is_empty() {
+            bail!("Nothing selected to choose");
+        }
+        // Not canonicalized, so a chosen symlink stays the link.
+        let paths = paths
+            .iter()
+            .map(|path| std::path::absolute(path).with_context(|| format!("Cannot resolve '{}'", name(path))))
+            .collect::<Result<_>>()?;
+        self.chosen = Some(paths);
+        Ok(())
+    }
+    fn begin_shell(&mut self) -> Result<()> {
+        self.ensure_not_read_only("Running shell commands")?;
+        if self.archive.is_some() {
+            bail!("Shell commands run in a directory, leave the archive first");
+        }
+        self.mode = AppMode::Shell;
+        Ok(())
+    }
+    /// Runs the `!` command in the current directory, either capturing its
+    /// output for a popup or in this terminal with the TUI suspended.
+    fn run_shell_command(&mut self) -> Result<()> {
+        let clipboard = match &self.clipboard {
+            Some(Clipboard::Path(path)) => Some(path.as_path()),
+            _ => None,
+        };
+        let command = shell::expand(self.shell_input.trim(), &self.selection(), &self.path, clipboard)?;
+        if command.is_empty() {
+            bail!("Type a command to run");
+        }
+        if self.shell_in_terminal {
+            self.interactive = Some(InteractiveCommand { command, dir: self.path.clone(), after_exit: AfterExit::WaitForEnter });
+        } else {
+            self.shell.spawn(&mut self.jobs, command, self.path.clone());
+        }
+        self.shell_input.clear();
+        self.mode = AppMode::Normal;
+        Ok(())
+    }
+    /// Ctrl+C: stops the `!` commands running in the background.
+    fn cancel_shell_commands(&mut self) -> Result<()> {
+        match self.shell.cancel_all() {
+            0 => bail!("No shell commands are running"),
+            1 => self.notification = Some("Cancelling the running command".to_string()),
+            count => self.notification = Some(format!("Cancelling {} running commands", count)),
         }
+        self.notification_time = Some(Instant::now());
+        Ok(())
+    }
+    /// Collapses (`[`) or expands (`]`) one more level of the JSON, YAML or
+    /// TOML tree in the preview. `None` means fully expanded.
+    fn adjust_fold_depth(&mut self, expand: bool) -> Result<()> {
+        let current = self.fold_depth.unwrap_or(self.tree_depth).min(self.tree_depth);
+        self.fold_depth = if expand {
+            Some(current + 1).filter(|&depth| depth < self.tree_depth)
+        } else {
+            Some(current.saturating_sub(1))
+        };
+        self.preview_cache = None;
         Ok(())
     }
     fn toggle_hidden_files(&mut self) -> Result<()> {
         self.show_hidden = !self.show_hidden;
-        self.files = Self::get_files(&self.path, self.show_hidden)?;
+        self.refresh_files()?;
         self.selected = 0;
         Ok(())
     }
     fn go_up_directory(&mut self) -> Result<()> {
+        if let Some(browser) = &mut self.archive {
+            let left = match browser.prefix.rfind('/') {
+                Some(i) => browser.prefix.split_off(i + 1),
+                None => std::mem::take(&mut browser.prefix),
+            };
+            browser.prefix.pop();
+            if left.is_empty() {
+                // Leaving the archive root returns to the directory holding the archive.
+                let archive_name = browser.archive_path.file_name().map(|name| name.to_string_lossy().to_string());
+                self.archive = None;
+                self.refresh_files()?;
+                self.selected = archive_name
+                    .and_then(|name| self.files.iter().position(|file| *file == name))
+                    .unwrap_or(0);
+            } else {
+                self.refresh_files()?;
+                self.selected = self.files.iter().position(|file| *file == left).unwrap_or(0);
+            }
+            return Ok(());
+        }
         let parent = self.path.parent().context("Already at root")?;
         self.path = parent.to_path_buf();
-        self.files = Self::get_files(&self.path, self.show_hidden)?;
+        self.files = Self::get_files(&self.path, self.show_hidden, self.sort)?;
         self.selected = 0;
         Ok(())
     }
 }
 fn ui(f: &mut Frame, app: &mut App) {
+    if let (AppMode::Pager, Some(pager)) = (&app.mode, &mut app.pager) {
+        render_pager(f, pager);
+        return;
+    }
     let main_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
@@ -234,6 +881,11 @@ fn ui(f: &mut Frame, app: &mut App) {
         .constraints([Constraint::Percentage(30), Constraint::Percentage(70)].as_ref())
         .split(main_chunks[1]);
     let file_list_width = content_chunks[0].width;
+    // A fresh list state scrolls just far enough to keep the selection on
+    // the last row, so these are the rows that get drawn.
+    let visible_rows = content_chunks[0].height.saturating_sub(2) as usize;
+    let first_row = (app.selected + 1).saturating_sub(visible_rows);
+    app.update_file_types(first_row..first_row + visible_rows);
     let file_list = render_file_list(app, file_list_width, &app.panel_focus);
     let mut state = ListState::default();
     state.select(Some(app.selected));
@@ -241,7 +893,7 @@ fn ui(f: &mut Frame, app: &mut App) {
     let right_chunks = Layout::default()
         .constraints([Constraint::Percentage(35), Constraint::Percentage(70)].as_ref())
         .split(content_chunks[1]);
-    let context_menu = render_context_menu(&app.panel_focus);
+    let context_menu = render_context_menu(&app.panel_focus, app.read_only);
     app.action_list_state.select(Some(app.selected_action));
     f.render_stateful_widget(context_menu, right_chunks[0], &mut app.action_list_state);
 
@@ -250,12 +902,7 @@ fn ui(f: &mut Frame, app: &mut App) {
         .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
         .split(right_chunks[1]);
 
-    let current_selected_path = if let Some(selected_file) = app.files.get(app.selected) {
-        Some(app.path.join(selected_file))
-    } else {
-        None
-    };
-    render_preview(f, right_panel_chunks[0], current_selected_path, app.last_highlight_time);
+    render_preview(f, right_panel_chunks[0], app);
     render_key_hints(f, right_panel_chunks[1]);
 
     if let Some(error_message) = &app.error_message {
@@ -328,11 +975,147 @@ fn ui(f: &mut Frame, app: &mut App) {
         f.set_cursor_position(Position::new(
             area.x + app.move_input.len() as u16 + 1,area.y + 1, ));
 }
+    if let AppMode::Highlight = app.mode {
+        let block = Block::default().title("Highlight regex (empty to clear)").borders(Borders::ALL);
+        let area = centered_rect(60, 20, f.area());
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+        let p = Paragraph::new(app.highlight_input.as_str());
+        f.render_widget(p, area);
+        f.set_cursor_position(Position::new(
+            area.x + app.highlight_input.len() as u16 + 1, area.y + 1,));
+    }
+    if let (AppMode::Database, Some(view)) = (&app.mode, &app.database) {
+        render_database_view(f, view);
+    }
+    if let AppMode::OpenWith = app.mode {
+        render_open_with(f, app);
+    }
+    if let AppMode::Shell = app.mode {
+        let how = if app.shell_in_terminal { "in the terminal" } else { "capturing output" };
+        let block = Block::default()
+            .title(format!("Shell command {} (Tab to switch; %f files, %d dir, %c clipboard)", how))
+            .borders(Borders::ALL);
+        let area = centered_rect(60, 20, f.area());
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+        let p = Paragraph::new(format!("!{}", app.shell_input));
+        f.render_widget(p, area);
+        f.set_cursor_position(Position::new(
+            area.x + app.shell_input.len() as u16 + 2, area.y + 1,));
+    }
+    if let Some(output) = &app.shell_output {
+        render_shell_output(f, output, app.shell_scroll);
+    }
+    if let AppMode::Compress = app.mode {
+        let kind = COMPRESS_FORMATS[app.compress_format];
+        let block = Block::default()
+            .title(format!("Compress as .{} (Tab to change format)", kind.name()))
+            .borders(Borders::ALL);
+        let area = centered_rect(60, 20, f.area());
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+        let p = Paragraph::new(app.compress_input.as_str());
+        f.render_widget(p, area);
+        f.set_cursor_position(Position::new(
+            area.x + app.compress_input.len() as u16 + 1, area.y + 1,));
+    }
+}
+fn render_database_view(f: &mut Frame, view: &DatabaseView) {
+    let area = centered_rect(90, 80, f.area());
+    f.render_widget(Clear, area);
+    let name = view.path.file_name().unwrap_or_default().to_string_lossy();
+    let block = Block::default()
+        .title(format!("{} (read-only, j/k to choose a table, Esc to close)", name))
+        .borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let [list_area, rows_area] = Layout::horizontal([Constraint::Percentage(25), Constraint::Percentage(75)]).areas(inner);
+    let items: Vec<ListItem> = view
+        .tables
+        .iter()
+        .map(|table| {
+            let count = table.rows.map(|rows| rows.to_string()).unwrap_or_else(|| table.kind.clone());
+            ListItem::new(Line::from(vec![
+                Span::raw(table.name.clone()),
+                Span::styled(format!(" ({})", count), Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().title("Tables").borders(Borders::RIGHT))
+        .highlight_style(Style::default().bg(Color::Rgb(70, 70, 70)).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    let mut state = ListState::default();
+    state.select(Some(view.selected));
+    f.render_stateful_widget(list, list_area, &mut state);
+    match &view.rows {
+        Some(Ok((columns, rows))) => {
+            let widths = structured::column_widths(columns, rows);
+            f.render_widget(data_table(columns.clone(), rows.clone(), widths), rows_area);
+        }
+        Some(Err(err)) => {
+            let p = Paragraph::new(format!("Cannot read table: {}", err)).style(Style::default().fg(Color::Red));
+            f.render_widget(p, rows_area);
+        }
+        None => f.render_widget(Paragraph::new("No tables in this database."), rows_area),
+    }
+}
+fn render_shell_output(f: &mut Frame, output: &ShellOutput, scroll: u16) {
+    let area = centered_rect(80, 70, f.area());
+    f.render_widget(Clear, area);
+    let status_style = if output.success { Style::default().fg(Color::Green) } else { Style::default().fg(Color::Red) };
+    let block = Block::default()
+        .title(Line::from(vec![
+            Span::raw(format!("$ {} ", output.command)),
+            Span::styled(format!("({})", output.status), status_style),
+        ]))
+        .title_bottom("j/k to scroll, Enter or Esc to close")
+        .borders(Borders::ALL);
+    // Only the lines on screen are handed to the widget; the output can be
+    // up to a megabyte.
+    let first = (scroll as usize).min(output.lines.len());
+    let height = block.inner(area).height as usize;
+    let lines = output.lines[first..].iter().take(height).cloned().collect::<Vec<_>>();
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+fn render_open_with(f: &mut Frame, app: &App) {
+    let area = centered_rect(60, 40, f.area());
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .title("Open with (or type a command, end with & to detach)")
+        .borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let [list_area, input_area] = Layout::vertical([Constraint::Min(1), Constraint::Length(2)]).areas(inner);
+    let mut items: Vec<ListItem> = app
+        .open_with
+        .iter()
+        .map(|opener| {
+            let kind = if opener.terminal { " (terminal)" } else { "" };
+            ListItem::new(Line::from(vec![
+                Span::raw(opener.name.clone()),
+                Span::styled(kind, Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+    items.push(ListItem::new("System default application"));
+    let list = List::new(items)
+        .highlight_style(Style::default().bg(Color::Rgb(70, 70, 70)).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    let mut state = ListState::default();
+    // A typed command wins over the list, so don't suggest otherwise.
+    state.select(app.open_with_input.is_empty().then_some(app.open_with_selected));
+    f.render_stateful_widget(list, list_area, &mut state);
+    let input = Paragraph::new(format!("Command: {}", app.open_with_input)).block(Block::default().borders(Borders::TOP));
+    f.render_widget(input, input_area);
+    f.set_cursor_position(Position::new(
+        input_area.x + "Command: ".len() as u16 + app.open_with_input.len() as u16, input_area.y + 1,));
 }
 fn render_key_hints(f: &mut Frame, area: Rect) {
     let mut spans = Vec::new();
     for (vim_key, arrow_key, _description) in VIM_KEY_HINTS.iter() { // Ignore description
-        spans.push(Span::styled(format!("{vim_key}"), Style::default().fg(Color::Yellow)));
+        spans.push(Span::styled(vim_key.to_string(), Style::default().fg(Color::Yellow)));
         spans.push(Span::raw("/"));
         spans.push(Span::styled(format!("{arrow_key:<10}"), Style::default().fg(Color::Cyan)));
         spans.push(Span::raw("  ")); // Add some spacing between hints
@@ -350,20 +1133,29 @@ fn render_key_hints(f: &mut Frame, area: Rect) {
 
 fn render_address_bar(app: &App, width: u16) -> Paragraph<'_> {
     let path_str = if app.mode == AppMode::Editing {
-        app.address_input.as_str()
+        app.address_input.clone()
+    } else if let Some(browser) = &app.archive {
+        format!("{} [read-only]", browser.display_path().display())
     } else {
-        app.path.to_str().unwrap_or("Karu")
+        app.path.to_str().unwrap_or("Karu").to_string()
     };
 
-    let mut spans = vec![Span::raw(path_str)];
+    let mut spans = vec![Span::raw(path_str.clone())];
 
-    if let Some(notification) = &app.notification {
+    // Notifications take precedence over the progress of running jobs.
+    let status = app.notification.clone().or_else(|| {
+        app.jobs.jobs.first().map(|job| match app.jobs.jobs.len() - 1 {
+            0 => format!("{} {}%", job.label, job.percent()),
+            more => format!("{} {}% (+{} more)", job.label, job.percent(), more),
+        })
+    });
+    if let Some(notification) = status {
+        let padding_len = (width as usize)
+            .saturating_sub(path_str.len())
+            .saturating_sub(notification.len())
+            .saturating_sub(2); // for borders
         let notification_span = Span::styled(
             notification,Style::default().fg(Color::Yellow), );
-        let padding_len = width as usize
-            - path_str.len()
-            - notification.len()
-            - 2; // for borders
         if padding_len > 0 {
             let padding = " ".repeat(padding_len);
             spans.push(Span::raw(padding));
@@ -371,31 +1163,34 @@ fn render_address_bar(app: &App, width: u16) -> Paragraph<'_> {
         spans.push(notification_span);
     }
 
+    let mut title = vec![Span::raw(match app.chooser {
+        Some(ChooseKind::Files) => "Choose files (Enter picks a file, Ctrl+Y the marked ones, q cancels)",
+        Some(ChooseKind::Dir) => "Choose a directory (Ctrl+Y picks the highlighted or current one, q cancels)",
+        None => "Address",
+    })];
+    if app.read_only {
+        title.push(Span::raw(" "));
+        title.push(Span::styled(" READ-ONLY ", Style::default().fg(Color::Black).bg(Color::Red).add_modifier(Modifier::BOLD)));
+    }
     Paragraph::new(Line::from(spans))
-        .block(Block::default().title("Address").borders(Borders::ALL))
+        .block(Block::default().title(Line::from(title)).borders(Borders::ALL))
 }
 fn render_file_list<'a>(app: &'a App, max_width: u16, panel_focus: &PanelFocus) -> List<'a> {
     let items: Vec<ListItem> = app
         .files
         .iter()
         .map(|i| {
-            let path = app.path.join(i);
-            let is_dir = path.is_dir();
-            let color = if is_dir {
-                Color::Rgb(0, 200, 128) // Dark Green
-            } else {
-                Color::Blue
-            };
-            let style = Style::default().fg(color);
-
-            let glyph = if is_dir {
-                md::MD_FOLDER_OPEN
-            } else {
-                md::MD_FILE
-            };
+            let file_type = app.entry_type(i);
+            let is_dir = file_type.category == Category::Directory;
+            let (glyph, mut style) = entry_glyph(file_type);
+            if app.archive.is_none() && app.marked.contains(&app.path.join(i)) {
+                style = style.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+            }
 
             let size_width = 10;
-            let name_width = (max_width as usize).saturating_sub(size_width + 4);
+            // The type column only fits in wider panels.
+            let type_width = if max_width >= 48 { 9 } else { 0 };
+            let name_width = (max_width as usize).saturating_sub(size_width + type_width + 4);
 
             let display_name_str = if i.chars().count() > name_width {
                 i.chars().take(name_width - 3).collect::<String>() + "..."
@@ -404,16 +1199,19 @@ fn render_file_list<'a>(app: &'a App, max_width: u16, panel_focus: &PanelFocus)
             };
 
             let name_part_width =
-                glyph.trim().chars().count() + 2 + display_name_str.chars().count();
+                glyph.chars().count() + 2 + display_name_str.chars().count();
             let padding_width = name_width.saturating_sub(name_part_width);
             let padding = " ".repeat(padding_width);
 
             let mut spans = vec![
-                Span::styled(glyph.trim(), style), Span::styled(format!("  {display_name_str}"), style), Span::raw(padding),];
+                Span::styled(glyph, style), Span::styled(format!("  {display_name_str}"), style), Span::raw(padding),];
 
+            if type_width > 0 && i != ".." {
+                let label: String = file_type.label().chars().take(type_width - 1).collect();
+                spans.push(Span::styled(format!("{:>width$}", label, width = type_width), Style::default().fg(Color::DarkGray)));
+            }
             if !is_dir {
-                if let Ok(metadata) = fs::metadata(&path) {
-                    let size = metadata.len();
+                if let Some(size) = app.entry_size(i) {
                     let formatted_size = format_size(size);
                     let padded_size = format!("{:>width$}", formatted_size, width = size_width);
                     spans.push(Span::raw(padded_size));
@@ -423,7 +1221,12 @@ fn render_file_list<'a>(app: &'a App, max_width: u16, panel_focus: &PanelFocus)
             ListItem::new(Line::from(spans))
         })
         .collect();
-    let mut list = List::new(items).block(Block::default().title("Files").borders(Borders::ALL));
+    let title = if app.marked.is_empty() {
+        "Files".to_string()
+    } else {
+        format!("Files ({} marked)", app.marked.len())
+    };
+    let mut list = List::new(items).block(Block::default().title(title).borders(Borders::ALL));
     list = list.highlight_style(Style::default().bg(Color::Rgb(70, 70, 70))); // A subtle background for selected item when not focused
 
     if let PanelFocus::Files = panel_focus {
@@ -436,10 +1239,24 @@ fn render_file_list<'a>(app: &'a App, max_width: u16, panel_focus: &PanelFocus)
     }
     list
 }
-fn render_context_menu(panel_focus: &PanelFocus) -> List<'_> {
+fn entry_glyph(file_type: FileType) -> (&'static str, Style) {
+    if file_type.category == Category::Directory {
+        (file_type.glyph(), Style::default().fg(Color::Rgb(0, 200, 128))) // Dark Green
+    } else {
+        (file_type.glyph(), Style::default().fg(Color::Blue))
+    }
+}
+fn render_context_menu(panel_focus: &PanelFocus, read_only: bool) -> List<'_> {
     let items: Vec<ListItem> = ACTIONS
         .iter()
-        .map(|(action, shortcut)| ListItem::new(format!("{} ({})", action, shortcut)))
+        .map(|(action, shortcut)| {
+            let item = ListItem::new(format!("{} ({})", action, shortcut));
+            if read_only && MUTATING_ACTIONS.contains(action) {
+                item.style(Style::default().fg(Color::DarkGray))
+            } else {
+                item
+            }
+        })
         .collect();
     let mut list = List::new(items)
         .block(
@@ -456,11 +1273,11 @@ fn render_context_menu(panel_focus: &PanelFocus) -> List<'_> {
     }
     list
 }
-fn render_preview(f: &mut Frame, area: Rect, path_to_preview: Option<PathBuf>, last_highlight_time: Instant) {
+fn render_preview(f: &mut Frame, area: Rect, app: &mut App) {
     f.render_widget(Clear, area);
 
     let current_time = Instant::now();
-    let elapsed_time = current_time.duration_since(last_highlight_time);
+    let elapsed_time = current_time.duration_since(app.last_highlight_time);
     const PREVIEW_DELAY_MS: u64 = 100;
 
     let path = if elapsed_time.as_millis() < PREVIEW_DELAY_MS as u128 {
@@ -468,106 +1285,719 @@ fn render_preview(f: &mut Frame, area: Rect, path_to_preview: Option<PathBuf>, l
             .block(Block::default().title("Preview").borders(Borders::ALL));
         f.render_widget(p, area);
         return;
-    } else if let Some(p) = path_to_preview {
-        p
+    } else if let Some(selected_file) = app.files.get(app.selected) {
+        if let Some(browser) = &app.archive {
+            render_archive_entry_preview(f, area, browser, selected_file, app.show_hidden, &mut app.preview_cache);
+            return;
+        }
+        app.path.join(selected_file)
     } else {
         return;
     };
+    if let Some(follower) = app.follow.as_ref().filter(|follower| follower.path == path) {
+        render_follow(f, area, follower);
+        return;
+    }
+    let show_hidden = app.show_hidden;
 
     // Explicitly block .wget-hsts file
-    if path.file_name().map_or(false, |name| name == ".wget-hsts") {
+    if path.file_name().is_some_and(|name| name == ".wget-hsts") {
         let p = Paragraph::new("'.wget-hsts' file is blocked from preview.")
             .block(Block::default().title("Preview").borders(Borders::ALL));
         f.render_widget(p, area);
         return; // Exit the function early
     }
 
+    if path.is_dir() {
+        render_directory_preview(f, area, &path, show_hidden, app.sort);
+        return;
+    }
+
     // Check file size for preview
-    if let Ok(metadata) = fs::metadata(&path) {
-        const MAX_PREVIEW_SIZE_MB: u64 = 300;
-        const MAX_PREVIEW_SIZE_BYTES: u64 = MAX_PREVIEW_SIZE_MB * 1024 * 1024; // 300 MB in bytes
-        if metadata.len() > MAX_PREVIEW_SIZE_BYTES {
-            let p = Paragraph::new(format!(
-                "File is too large for preview ({}) Max size is {} MB.", format_size(metadata.len()), MAX_PREVIEW_SIZE_MB
-            ))
-            .block(Block::default().title("Preview").borders(Borders::ALL));
-            f.render_widget(p, area);
-            return; // Exit the function early
-        }
+    let file_size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    const MAX_PREVIEW_SIZE_MB: u64 = 300;
+    const MAX_PREVIEW_SIZE_BYTES: u64 = MAX_PREVIEW_SIZE_MB * 1024 * 1024; // 300 MB in bytes
+    if file_size > MAX_PREVIEW_SIZE_BYTES {
+        let p = Paragraph::new(format!(
+            "File is too large for preview ({}) Max size is {} MB.", format_size(file_size), MAX_PREVIEW_SIZE_MB
+        ))
+        .block(Block::default().title("Preview").borders(Borders::ALL));
+        f.render_widget(p, area);
+        return; // Exit the function early
     }
-    if is_image(&path) {
-        if let Ok(_img) = image::open(&path) {
-            let inner_area = area.inner(Margin {
-                horizontal: 1, vertical: 1,});
-            let config = viuer::Config {
-                x: inner_area.x, y: inner_area.y as i16, width: Some(inner_area.width as u32), height: Some(inner_area.height as u32), ..Default::default()
-            };
-            viuer::print_from_file(path, &config).expect("Image printing failed.");
-            // Draw the block and borders after the image to make them visible
-            let block = Block::default().title("Preview").borders(Borders::ALL).style(Style::default().bg(Color::Reset));
-            f.render_widget(block, area);
-        } else {
-            let p = Paragraph::new("Could not load image")
-                .block(Block::default().title("Preview").borders(Borders::ALL));
-            f.render_widget(p, area);
-        }
-    } else if is_likely_binary(&path) {
-        let p = Paragraph::new("Binary file, no preview available.")
-            .block(Block::default().title("Preview").borders(Borders::ALL));
+    let file_type = app.file_types.update(&path);
+    let inner = area.inner(Margin::new(1, 1));
+    if let Some(preview) = app.previewers.preview(&path, file_type.mime, inner.width, inner.height) {
+        let (title, text) = match preview {
+            ExternalPreview::Running(command) => (
+                "Preview (running previewer)".to_string(),
+                Text::styled(format!("Running '{}'...", command), Style::default().fg(Color::DarkGray)),
+            ),
+            ExternalPreview::Done(text) => ("Preview (external)".to_string(), text.clone()),
+            ExternalPreview::Failed(err) => ("Preview (external)".to_string(), Text::styled(err.to_string(), Style::default().fg(Color::Red))),
+        };
+        let p = Paragraph::new(text).block(Block::default().title(title).borders(Borders::ALL));
+        f.render_widget(p, area);
+        return;
+    }
+    if let Some(kind) = archive_kind(&path, file_type) {
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let preview = cached_preview(&mut app.preview_cache, &path, modified, || archive_listing(&path, kind));
+        let p = Paragraph::new(preview.content.clone())
+            .block(Block::default().title(preview.title.clone()).borders(Borders::ALL));
+        f.render_widget(p, area);
+    } else if (file_type.category == Category::Image && image::ImageFormat::from_mime_type(file_type.mime).is_some())
+        || (file_type.mime == "image/svg+xml" && !app.raw_preview)
+    {
+        let title = if file_type.mime == "image/svg+xml" { "Preview (SVG, Shift+R for source)" } else { "Preview" };
+        let block = Block::default().title(title).borders(Borders::ALL);
+        let inner_area = block.inner(area);
+        f.render_widget(block, area);
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let metadata = cached_preview(&mut app.preview_cache, &path, modified, || {
+            let text = match image_preview::image_metadata(&path) {
+                Ok(fields) => metadata_text(&fields),
+                Err(err) => Text::styled(format!("No metadata: {}", err), Style::default().fg(Color::DarkGray)),
+            };
+            ("Metadata".to_string(), text)
+        });
+        let metadata_height = (metadata.content.height() as u16 + 1).min(inner_area.height / 2);
+        let [image_area, metadata_area] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(metadata_height)]).areas(inner_area);
+        let p = Paragraph::new(metadata.content.clone())
+            .block(Block::default().title(metadata.title.clone()).borders(Borders::TOP));
+        f.render_widget(p, metadata_area);
+        let inner_area = image_area;
+        // Escape-based images sit on top of the cell grid and would cover popups.
+        if app.image.uses_escapes() && app.has_popup() {
+            f.render_widget(Paragraph::new("Image preview paused while a dialog is open."), inner_area);
+        } else if let Err(err) = app.image.render(&path, inner_area, f.buffer_mut()) {
+            let p = Paragraph::new(format!("Could not load image: {}", err))
+                .style(Style::default().fg(Color::Red))
+                .wrap(ratatui::widgets::Wrap { trim: true });
+            f.render_widget(p, inner_area);
+        }
+    } else if matches!(file_type.category, Category::Audio | Category::Video) {
+        let block = Block::default().title("Preview").borders(Borders::ALL);
+        let inner_area = block.inner(area);
+        f.render_widget(block, area);
+        let has_popup = app.has_popup();
+        let info = match app.media.get(&path, file_type.mime) {
+            Ok(info) => info,
+            Err(err) => {
+                let p = Paragraph::new(format!("Cannot read media file: {}", err))
+                    .style(Style::default().fg(Color::Red))
+                    .wrap(ratatui::widgets::Wrap { trim: true });
+                f.render_widget(p, inner_area);
+                return;
+            }
+        };
+        let text = metadata_text(&info.fields);
+        let Some(cover) = &info.cover else {
+            f.render_widget(Paragraph::new(text), inner_area);
+            return;
+        };
+        let metadata_height = (text.height() as u16 + 1).min(inner_area.height / 2);
+        let [cover_area, metadata_area] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(metadata_height)]).areas(inner_area);
+        let p = Paragraph::new(text).block(Block::default().title("Metadata").borders(Borders::TOP));
+        f.render_widget(p, metadata_area);
+        if app.image.uses_escapes() && has_popup {
+            f.render_widget(Paragraph::new("Cover art paused while a dialog is open."), cover_area);
+        } else if let Err(err) = app.image.render_with(&path, cover_area, f.buffer_mut(), || Ok(image::load_from_memory(cover)?)) {
+            let p = Paragraph::new(format!("Could not load cover art: {}", err)).style(Style::default().fg(Color::Red));
+            f.render_widget(p, cover_area);
+        }
+    } else if file_type.mime == "text/markdown" && !app.raw_preview && file_size <= MAX_STRUCTURED_PREVIEW_BYTES {
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let width = area.width.saturating_sub(2) as usize;
+        let preview = cached_preview(&mut app.preview_cache, &path, modified, || {
+            let title = "Preview (Markdown, Shift+R for source)".to_string();
+            match encoding::read_file(&path, u64::MAX) {
+                Ok(source) => (title, Text::from(markdown::render(&source.text, width))),
+                Err(err) => (title, Text::styled(format!("Cannot read file: {}", err), Style::default().fg(Color::Red))),
+            }
+        });
+        let p = Paragraph::new(preview.content.clone())
+            .block(Block::default().title(preview.title.clone()).borders(Borders::ALL))
+            .wrap(ratatui::widgets::Wrap { trim: false });
+        f.render_widget(p, area);
+    } else if let Some(format) = DataFormat::from_mime(file_type.mime).filter(|_| file_size <= MAX_STRUCTURED_PREVIEW_BYTES) {
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let fold_depth = app.fold_depth;
+        let tree_depth = &mut app.tree_depth;
+        let preview = cached_preview(&mut app.preview_cache, &path, modified, || {
+            structured_preview(&path, format, fold_depth, tree_depth)
+        });
+        let p = Paragraph::new(preview.content.clone())
+            .block(Block::default().title(preview.title.clone()).borders(Borders::ALL));
+        f.render_widget(p, area);
+    } else if let Some(delimiter) = structured::table_delimiter(file_type.mime) {
+        render_table_preview(f, area, &path, delimiter, &mut app.table_cache);
+    } else if file_type.category == Category::Database {
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let preview = cached_preview(&mut app.preview_cache, &path, modified, || database_summary(&path));
+        let p = Paragraph::new(preview.content.clone())
+            .block(Block::default().title(preview.title.clone()).borders(Borders::ALL));
+        f.render_widget(p, area);
+    } else if let Some(kind) = document::DocumentKind::from_mime(file_type.mime) {
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let preview = cached_preview(&mut app.preview_cache, &path, modified, || match document::extract(&path, kind) {
+            Ok(doc) => {
+                let title = match doc.summary {
+                    Some(summary) => format!("Preview ({}, {})", kind.name(), summary),
+                    None => format!("Preview ({})", kind.name()),
+                };
+                (title, Text::raw(doc.text))
+            }
+            Err(err) => (
+                format!("Preview ({})", kind.name()),
+                Text::styled(format!("Cannot read document: {:#}", err), Style::default().fg(Color::Red)),
+            ),
+        });
+        let p = Paragraph::new(preview.content.clone())
+            .block(Block::default().title(preview.title.clone()).borders(Borders::ALL))
+            .wrap(ratatui::widgets::Wrap { trim: false });
+        f.render_widget(p, area);
+    } else if file_type.category == Category::Executable {
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let preview = cached_preview(&mut app.preview_cache, &path, modified, || executable_summary(&path));
+        let p = Paragraph::new(preview.content.clone())
+            .block(Block::default().title(preview.title.clone()).borders(Borders::ALL));
+        f.render_widget(p, area);
+    } else if !file_type.is_text() {
+        let p = Paragraph::new("Binary file, no preview available.")
+            .block(Block::default().title("Preview").borders(Borders::ALL));
         f.render_widget(p, area);
     } else {
         let block = Block::default().style(Style::default().bg(Color::Reset));
         f.render_widget(block, area);
 
-        let content = if path.is_dir() {
-            "Directory".to_string()
-        } else {
-            fs::read_to_string(path).unwrap_or_else(|err| format!("Cannot read file: {}", err))
-        };
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
         let max_width = area.width.saturating_sub(2) as usize;
-        let truncated_content: String = content
-            .lines()
-            .map(|line| {
-                if line.len() > max_width {
-                    format!("{}\"...", &line[0..max_width.saturating_sub(3)])
-                } else {
-                    line.to_string()
-                }
-            })
-            .collect::<Vec<String>>()
-            .join("\n");
-        let p = Paragraph::new(truncated_content)
-            .block(Block::default().title("Preview").borders(Borders::ALL))
+        let preview = cached_preview(&mut app.preview_cache, &path, modified, || text_preview(&path, max_width));
+        let p = Paragraph::new(preview.content.clone())
+            .block(Block::default().title(preview.title.clone()).borders(Borders::ALL))
             .style(Style::default().bg(Color::Reset));
         f.render_widget(p, area);
     }
 }
-fn is_image(path: &Path) -> bool {
-    let extension = path.extension().and_then(|s| s.to_str());
-    if let Some(ext) = extension {
-        matches!(
-            ext.to_lowercase().as_str(),"png" | "jpg" | "jpeg" | "gif" | "bmp" | "ico" | "tiff" | "webp"
-        )
-    } else {
-        false
+/// The last lines of a followed file, newest at the bottom, with matches of
+/// the highlight pattern picked out.
+fn render_follow(f: &mut Frame, area: Rect, follower: &Follower) {
+    let state = if follower.paused { "paused, Shift+P to resume" } else { "following, Shift+P to pause" };
+    let title = match &follower.highlight {
+        Some(regex) => format!("Follow ({}, highlighting /{}/)", state, regex),
+        None => format!("Follow ({})", state),
+    };
+    let block = Block::default().title(title).borders(Borders::ALL);
+    let height = block.inner(area).height as usize;
+    let pending = follower.pending();
+    let texts = follower
+        .lines
+        .iter()
+        .map(|line| match line {
+            FollowLine::Text(text) => (text.as_str(), false),
+            FollowLine::Event(text) => (text.as_str(), true),
+        })
+        .chain(pending.as_deref().map(|text| (text, false)));
+    let count = follower.lines.len() + pending.is_some() as usize;
+    let lines: Vec<Line> = texts
+        .skip(count.saturating_sub(height))
+        .map(|(text, is_event)| {
+            if is_event {
+                return Line::styled(text.to_string(), Style::default().fg(Color::Yellow));
+            }
+            let Some(regex) = &follower.highlight else {
+                return Line::raw(text.to_string());
+            };
+            let mut spans = Vec::new();
+            let mut last = 0;
+            for found in regex.find_iter(text).filter(|found| !found.is_empty()) {
+                spans.push(Span::raw(text[last..found.start()].to_string()));
+                spans.push(Span::styled(
+                    found.as_str().to_string(),
+                    Style::default().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD),
+                ));
+                last = found.end();
+            }
+            spans.push(Span::raw(text[last..].to_string()));
+            Line::from(spans)
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+/// Everything the preview would show for `path`, without the preview's
+/// size limits, for the full-screen viewer.
+fn full_preview(
+    path: &Path,file_type: FileType,raw: bool,fold_depth: Option<usize>,tree_depth: &mut usize,width: usize,
+) -> (String, Text<'static>) {
+    const MAX_TEXT_BYTES: u64 = 16 * 1024 * 1024;
+    const MAX_HEX_BYTES: u64 = 4 * 1024 * 1024;
+    let error = |title: &str, err: &dyn std::fmt::Display| {
+        (title.to_string(), Text::styled(format!("Cannot read file: {}", err), Style::default().fg(Color::Red)))
+    };
+    if let Some(kind) = archive_kind(path, file_type) {
+        return archive_listing(path, kind);
+    }
+    match file_type.category {
+        Category::Database => return database_summary(path),
+        Category::Executable => return executable_summary(path),
+        Category::Audio | Category::Video => {
+            return match media::probe(path, file_type.mime) {
+                Ok(info) => ("Preview (media)".to_string(), metadata_text(&info.fields)),
+                Err(err) => error("Preview (media)", &err),
+            };
+        }
+        Category::Image if image::ImageFormat::from_mime_type(file_type.mime).is_some() => {
+            return match image_preview::image_metadata(path) {
+                Ok(fields) => ("Preview (image)".to_string(), metadata_text(&fields)),
+                Err(err) => error("Preview (image)", &err),
+            };
+        }
+        _ => {}
+    }
+    let file_size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    if file_type.mime == "text/markdown" && !raw && file_size <= MAX_STRUCTURED_PREVIEW_BYTES {
+        return match encoding::read_file(path, MAX_TEXT_BYTES) {
+            Ok(source) => ("Preview (Markdown)".to_string(), Text::from(markdown::render(&source.text, width))),
+            Err(err) => error("Preview (Markdown)", &err),
+        };
+    }
+    if let Some(format) = DataFormat::from_mime(file_type.mime).filter(|_| file_size <= MAX_STRUCTURED_PREVIEW_BYTES) {
+        return structured_preview(path, format, fold_depth, tree_depth);
+    }
+    if let Some(kind) = document::DocumentKind::from_mime(file_type.mime) {
+        let title = format!("Preview ({})", kind.name());
+        return match document::extract(path, kind) {
+            Ok(document) => (title, plain_text(&document.text)),
+            Err(err) => error(&title, &err),
+        };
+    }
+    if file_type.is_text() {
+        return match encoding::read_file(path, MAX_TEXT_BYTES) {
+            Ok(decoded) => {
+                let text = if decoded.line_ending == Some("CR") { decoded.text.replace('\r', "\n") } else { decoded.text.clone() };
+                (format!("Preview ({})", decoded.summary()), plain_text(&text))
+            }
+            Err(err) => error("Preview", &err),
+        };
+    }
+    let mut data = Vec::new();
+    match fs::File::open(path).and_then(|file| io::Read::read_to_end(&mut io::Read::take(file, MAX_HEX_BYTES), &mut data)) {
+        Ok(_) => ("Preview (hex)".to_string(), Text::from(pager::hex_lines(&data))),
+        Err(err) => error("Preview (hex)", &err),
     }
 }
+/// One line per line of `text`, with tabs expanded so columns line up.
+fn plain_text(text: &str) -> Text<'static> {
+    Text::from(text.lines().map(|line| Line::raw(line.replace('\t', "    "))).collect::<Vec<_>>())
+}
+fn render_pager(f: &mut Frame, pager: &mut Pager) {
+    let area = f.area();
+    f.render_widget(Clear, area);
+    let selection = pager.selected_range().filter(|_| pager.anchor.is_some());
+    let mut status = format!("line {}/{}", pager.cursor + 1, pager.lines.len());
+    if let Some((first, last)) = selection {
+        status.push_str(&format!(", {} selected", last - first + 1));
+    }
+    if pager.wrap {
+        status.push_str(", wrap");
+    }
+    let block = Block::default().title(format!("{} ({})", pager.title, status)).borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let [text_area, status_area] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+    pager.height = (text_area.height as usize).max(1);
+    let gutter = pager.lines.len().max(1).to_string().len() + 1;
+    let width = (text_area.width as usize).saturating_sub(gutter).max(1);
+    let row_count = |pager: &Pager, index: usize| {
+        if pager.wrap { pager::wrap_columns(&pager.lines[index], width).len() } else { 1 }
+    };
+    // Wrapped lines can take several rows; scroll until the cursor line fits.
+    while pager.top < pager.cursor && (pager.top..=pager.cursor).map(|index| row_count(pager, index)).sum::<usize>() > pager.height {
+        pager.top += 1;
+    }
+    let mut rows = Vec::new();
+    for index in pager.top..pager.lines.len() {
+        if rows.len() >= pager.height {
+            break;
+        }
+        let mut line = pager.lines[index].clone();
+        if let Some(regex) = &pager.search {
+            line = pager::highlight_matches(&line, pager.plain(index), regex);
+        }
+        let segments = if pager.wrap { pager::wrap_columns(&line, width) } else { vec![pager::slice_columns(&line, pager.left, width)] };
+        let background = if selection.is_some_and(|(first, last)| (first..=last).contains(&index)) {
+            Some(Color::Rgb(50, 60, 90))
+        } else if index == pager.cursor {
+            Some(Color::Rgb(50, 50, 50))
+        } else {
+            None
+        };
+        for (segment_index, segment) in segments.into_iter().enumerate() {
+            let number = if segment_index == 0 { format!("{:>width$} ", index + 1, width = gutter - 1) } else { " ".repeat(gutter) };
+            let number_style = if index == pager.cursor { Style::default().fg(Color::Yellow) } else { Style::default().fg(Color::DarkGray) };
+            let mut spans = vec![Span::styled(number, number_style)];
+            spans.extend(segment.spans);
+            let mut row = Line::from(spans).style(segment.style);
+            if let Some(background) = background {
+                row = row.patch_style(Style::default().bg(background));
+            }
+            rows.push(row);
+        }
+    }
+    rows.truncate(pager.height);
+    f.render_widget(Paragraph::new(rows), text_area);
+    let status_line = match (&pager.prompt, &pager.message) {
+        (Some((PagerPrompt::Search, input)), _) => Line::raw(format!("/{}", input)),
+        (Some((PagerPrompt::GotoLine, input)), _) => Line::raw(format!(":{}", input)),
+        (None, Some(message)) => Line::styled(message.clone(), Style::default().fg(Color::Yellow)),
+        (None, None) => Line::styled(
+            "j/k scroll  / search  n/N next/prev  : go to line  w wrap  v select  y yank  q close",
+            Style::default().fg(Color::DarkGray),
+        ),
+    };
+    f.render_widget(Paragraph::new(status_line), status_area);
+    if let Some((_, input)) = &pager.prompt {
+        f.set_cursor_position(Position::new(status_area.x + input.len() as u16 + 1, status_area.y));
+    }
+}
+/// Plain text preview with the detected encoding and line endings in the
+/// title. Only the start of the file is read since that is all that shows.
+fn text_preview(path: &Path, max_width: usize) -> (String, Text<'static>) {
+    const MAX_TEXT_PREVIEW_BYTES: u64 = 1024 * 1024;
+    let decoded = match encoding::read_file(path, MAX_TEXT_PREVIEW_BYTES) {
+        Ok(decoded) => decoded,
+        Err(err) => return ("Preview".to_string(), Text::raw(format!("Cannot read file: {}", err))),
+    };
+    let title = format!("Preview ({})", decoded.summary());
+    let content = if decoded.line_ending == Some("CR") { decoded.text.replace('\r', "\n") } else { decoded.text };
+    let truncated_content: String = content
+        .lines()
+        .map(|line| {
+            if line.chars().count() > max_width {
+                line.chars().take(max_width.saturating_sub(3)).collect::<String>() + "..."
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    (title, Text::raw(truncated_content))
+}
+/// Label/value pairs with the labels lined up in a column.
+fn metadata_text(fields: &[(&str, String)]) -> Text<'static> {
+    let lines: Vec<Line> = fields
+        .iter()
+        .map(|(label, value)| {
+            Line::from(vec![
+                Span::styled(format!("{:<13}", label), Style::default().fg(Color::Cyan)),
+                Span::raw(value.clone()),
+            ])
+        })
+        .collect();
+    Text::from(lines)
+}
+fn render_directory_preview(f: &mut Frame, area: Rect, path: &Path, show_hidden: bool, sort: SortMode) {
+    let block = Block::default().title("Preview").borders(Borders::ALL);
+    let entries = match App::get_files(path, show_hidden, sort) {
+        Ok(files) => files,
+        Err(err) => {
+            let message = match err.downcast_ref::<io::Error>() {
+                Some(io_err) if io_err.kind() == io::ErrorKind::PermissionDenied => {
+                    format!("Permission denied: cannot list '{}'", path.display())
+                }
+                _ => format!("Cannot read directory: {}", err),
+            };
+            let p = Paragraph::new(message)
+                .block(block)
+                .style(Style::default().fg(Color::Red));
+            f.render_widget(p, area);
+            return;
+        }
+    };
 
-fn is_likely_binary(path: &Path) -> bool {
-    if path.is_dir() {
-        return false;
+    let children = entries
+        .into_iter()
+        .filter(|name| name.as_str() != "..")
+        .map(|name| {
+            let entry_path = path.join(&name);
+            let is_dir = entry_path.is_dir();
+            let size = if is_dir { None } else { fs::metadata(&entry_path).ok().map(|m| m.len()) };
+            (name, is_dir, size)
+        });
+    let lines = directory_listing(children, area.width.saturating_sub(2) as usize);
+    let p = Paragraph::new(lines).block(block);
+    f.render_widget(p, area);
+}
+/// Summary line followed by one row per child, shared by the directory and
+/// archive-directory previews.
+fn directory_listing(children: impl Iterator<Item = (String, bool, Option<u64>)>, max_width: usize) -> Vec<Line<'static>> {
+    let size_width = 10;
+    let name_width = max_width.saturating_sub(size_width + 3);
+    let mut dir_count = 0;
+    let mut file_count = 0;
+    let mut total_size = 0;
+    let mut lines = Vec::new();
+    for (name, is_dir, size) in children {
+        let (glyph, style) = entry_glyph(FileType::guess(Path::new(&name), is_dir));
+        let display_name: String = if name.chars().count() > name_width {
+            name.chars().take(name_width.saturating_sub(3)).collect::<String>() + "..."
+        } else {
+            name.clone()
+        };
+        let mut spans = vec![
+            Span::styled(glyph, style),
+            Span::styled(format!(" {:<width$}", display_name, width = name_width), style),
+        ];
+        if is_dir {
+            dir_count += 1;
+        } else {
+            file_count += 1;
+            if let Some(size) = size {
+                total_size += size;
+                spans.push(Span::raw(format!("{:>width$}", format_size(size), width = size_width)));
+            }
+        }
+        lines.push(Line::from(spans));
+    }
+
+    let summary = format!(
+        "{} {}, {} {}, {} total",
+        dir_count,
+        if dir_count == 1 { "directory" } else { "directories" },
+        file_count,
+        if file_count == 1 { "file" } else { "files" },
+        format_size(total_size)
+    );
+    if lines.is_empty() {
+        lines.push(Line::styled("Empty directory", Style::default().fg(Color::DarkGray)));
+    }
+    lines.insert(0, Line::styled(summary, Style::default().fg(Color::Yellow)));
+    lines.insert(1, Line::raw(""));
+    lines
+}
+fn cached_preview<'a, T>(
+    cache: &'a mut Option<CachedPreview<T>>,key: &Path,modified: Option<SystemTime>,build: impl FnOnce() -> (String, T),
+) -> &'a CachedPreview<T> {
+    let is_fresh = matches!(cache, Some(cached) if cached.key == key && cached.modified == modified);
+    if !is_fresh {
+        let (title, content) = build();
+        *cache = Some(CachedPreview { key: key.to_path_buf(), modified, title, content });
+    }
+    cache.as_ref().expect("preview cache was just filled")
+}
+fn archive_listing(path: &Path, kind: ArchiveKind) -> (String, Text<'static>) {
+    let title = format!("Preview ({} archive)", kind.name());
+    let entries = match archive::list_entries(path, kind) {
+        Ok(entries) => entries,
+        Err(err) => return (title, Text::styled(format!("Cannot read archive: {}", err), Style::default().fg(Color::Red))),
+    };
+    let file_count = entries.iter().filter(|e| !e.is_dir).count();
+    let total_size: u64 = entries.iter().map(|e| e.size).sum();
+    let mut lines = vec![
+        Line::styled(
+            format!("{} entries, {} files, {} uncompressed", entries.len(), file_count, format_size(total_size)),
+            Style::default().fg(Color::Yellow),
+        ),
+        Line::raw(""),
+    ];
+    for entry in &entries {
+        let modified = entry.modified.map(|t| t.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default();
+        let size = if entry.is_dir { String::new() } else { format_size(entry.size) };
+        let (_, style) = entry_glyph(FileType::guess(Path::new(&entry.path), entry.is_dir));
+        let name = if entry.is_dir { format!("{}/", entry.path) } else { entry.path.clone() };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:<16}", modified), Style::default().fg(Color::DarkGray)),
+            Span::raw(format!(" {:>10}  ", size)),
+            Span::styled(name, style),
+        ]));
+    }
+    (title, Text::from(lines))
+}
+/// Tables of an SQLite database with their row counts and schemas.
+fn database_summary(path: &Path) -> (String, Text<'static>) {
+    let tables = match database::tables(path) {
+        Ok(tables) => tables,
+        Err(err) => {
+            let text = Text::styled(format!("Cannot open database: {}", err), Style::default().fg(Color::Red));
+            return ("Preview (SQLite)".to_string(), text);
+        }
+    };
+    let title = format!("Preview (SQLite, {} tables, Enter to browse)", tables.len());
+    let mut lines = Vec::new();
+    for table in tables {
+        let count = table.rows.map(|rows| format!("{} rows", rows)).unwrap_or_default();
+        let mut spans = vec![Span::styled(table.name, Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))];
+        if table.kind != "table" {
+            spans.push(Span::styled(format!(" ({})", table.kind), Style::default().fg(Color::DarkGray)));
+        }
+        spans.push(Span::styled(format!("  {}", count), Style::default().fg(Color::Yellow)));
+        lines.push(Line::from(spans));
+        for sql_line in table.sql.lines() {
+            lines.push(Line::styled(format!("  {}", sql_line.trim_end()), Style::default().fg(Color::DarkGray)));
+        }
+        lines.push(Line::raw(""));
     }
-    let mut file = match fs::File::open(path) {
-        Ok(file) => file, Err(_) => return false,};
-    let mut buffer = [0; 1024];
-    let n = match file.read(&mut buffer) {
-        Ok(n) => n, Err(_) => return false,};
-    for &byte in &buffer[..n] {
-        if byte == 0 {
-            return true;
+    (title, Text::from(lines))
+}
+/// Header fields, linked libraries and sections of a compiled binary.
+fn executable_summary(path: &Path) -> (String, Text<'static>) {
+    let info = match executable::inspect(path) {
+        Ok(info) => info,
+        Err(err) => {
+            let text = Text::styled(format!("Cannot parse binary: {:#}", err), Style::default().fg(Color::Red));
+            return ("Preview (binary)".to_string(), text);
+        }
+    };
+    let heading = |text: String| Line::styled(text, Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+    let mut lines = metadata_text(&info.fields).lines;
+    if !info.libraries.is_empty() {
+        lines.push(Line::raw(""));
+        lines.push(heading(format!("Libraries ({})", info.libraries.len())));
+        lines.extend(info.libraries.into_iter().map(|library| Line::raw(format!("  {}", library))));
+    }
+    if !info.sections.is_empty() {
+        lines.push(Line::raw(""));
+        lines.push(heading(format!("Sections ({})", info.sections.len())));
+        let name_width = info.sections.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+        for (name, size) in info.sections {
+            lines.push(Line::from(vec![
+                Span::raw(format!("  {:<width$}  ", name, width = name_width)),
+                Span::styled(format_size(size), Style::default().fg(Color::DarkGray)),
+            ]));
+        }
+    }
+    (format!("Preview ({})", info.format), Text::from(lines))
+}
+/// Tree view of a JSON, YAML or TOML file, or the parse error with the
+/// offending line highlighted in the raw text.
+fn structured_preview(path: &Path, format: DataFormat, fold_depth: Option<usize>, tree_depth: &mut usize) -> (String, Text<'static>) {
+    const MAX_LINES: usize = 2000;
+    let content = match encoding::read_file(path, u64::MAX) {
+        Ok(decoded) => decoded.text,
+        Err(err) => {
+            let title = format!("Preview ({})", format.name());
+            return (title, Text::styled(format!("Cannot read file: {}", err), Style::default().fg(Color::Red)));
+        }
+    };
+    match structured::parse(&content, format) {
+        Ok(node) => {
+            *tree_depth = node.depth();
+            let depth = fold_depth.unwrap_or(*tree_depth).min(*tree_depth);
+            let title = format!("Preview ({}, {}/{} levels, [ ] to fold)", format.name(), depth, tree_depth);
+            (title, Text::from(structured::tree_lines(&node, depth, MAX_LINES)))
+        }
+        Err(err) => {
+            let title = format!("Preview ({}, parse error)", format.name());
+            let location = err.line.map(|line| format!("Line {}: ", line)).unwrap_or_default();
+            let mut lines = vec![
+                Line::styled(format!("{}{}", location, err.message), Style::default().fg(Color::Red)),
+                Line::raw(""),
+            ];
+            // Start a few lines above the error so it is visible in the pane.
+            let first = err.line.map_or(0, |line| line.saturating_sub(4));
+            for (index, line) in content.lines().enumerate().skip(first).take(MAX_LINES) {
+                let style = if err.line == Some(index + 1) {
+                    Style::default().fg(Color::White).bg(Color::Red)
+                } else {
+                    Style::default()
+                };
+                lines.push(Line::from(vec![
+                    Span::styled(format!("{:>5} ", index + 1), Style::default().fg(Color::DarkGray)),
+                    Span::styled(line.to_string(), style),
+                ]));
+            }
+            (title, Text::from(lines))
         }
     }
-    false
+}
+fn render_table_preview(f: &mut Frame, area: Rect, path: &Path, delimiter: u8, cache: &mut Option<CachedPreview<Result<TableData>>>) {
+    // Read once per file version; only the rows that fit are drawn.
+    const MAX_TABLE_ROWS: usize = 500;
+    let kind = if delimiter == b'\t' { "TSV" } else { "CSV" };
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+    let preview = cached_preview(cache, path, modified, || match structured::read_table(path, delimiter, MAX_TABLE_ROWS) {
+        Ok(table) => (format!("Preview ({}, {} columns, {})", kind, table.widths.len(), table.encoding), Ok(table)),
+        Err(err) => (format!("Preview ({})", kind), Err(err)),
+    });
+    let table = match &preview.content {
+        Ok(table) => table,
+        Err(err) => {
+            let p = Paragraph::new(format!("Cannot parse {}: {:#}", kind, err))
+                .block(Block::default().title(preview.title.clone()).borders(Borders::ALL))
+                .style(Style::default().fg(Color::Red));
+            f.render_widget(p, area);
+            return;
+        }
+    };
+    let max_rows = area.height.saturating_sub(3) as usize;
+    let rows = table.rows.iter().take(max_rows).cloned().collect();
+    let widget = data_table(table.header.clone(), rows, table.widths.clone())
+        .block(Block::default().title(preview.title.clone()).borders(Borders::ALL));
+    f.render_widget(widget, area);
+}
+/// Table with a highlighted header row, shared by the CSV and database views.
+fn data_table(header: Vec<String>, rows: Vec<Vec<String>>, widths: Vec<u16>) -> Table<'static> {
+    let header = Row::new(header.into_iter().map(Cell::from))
+        .style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+    let rows = rows.into_iter().map(|row| Row::new(row.into_iter().map(Cell::from)));
+    Table::new(rows, widths.into_iter().map(Constraint::Length))
+        .header(header)
+        .column_spacing(2)
+}
+fn render_archive_entry_preview(
+    f: &mut Frame,area: Rect,browser: &ArchiveBrowser,name: &str,show_hidden: bool,cache: &mut Option<CachedPreview>,
+) {
+    let block = Block::default().title("Preview").borders(Borders::ALL);
+    if name == ".." {
+        f.render_widget(block, area);
+        return;
+    }
+    let entry_path = browser.entry_path(name);
+    if browser.is_dir(&entry_path) {
+        let children = browser.children(&entry_path, show_hidden).into_iter().map(|child| {
+            let child_path = format!("{}/{}", entry_path, child);
+            let is_dir = browser.is_dir(&child_path);
+            let size = if is_dir { None } else { browser.entry(&child_path).map(|e| e.size) };
+            (child, is_dir, size)
+        });
+        let lines = directory_listing(children, area.width.saturating_sub(2) as usize);
+        f.render_widget(Paragraph::new(lines).block(block), area);
+        return;
+    }
+    const MAX_ENTRY_PREVIEW_BYTES: u64 = 64 * 1024;
+    let modified = fs::metadata(&browser.archive_path).and_then(|m| m.modified()).ok();
+    let key = browser.archive_path.join(&entry_path);
+    let preview = cached_preview(cache, &key, modified, || {
+        match archive::read_entry(&browser.archive_path, browser.kind, &entry_path, MAX_ENTRY_PREVIEW_BYTES) {
+            Ok(data) if !filetype::detect_bytes(&data, Path::new(&entry_path)).is_text() => {
+                ("Preview".to_string(), Text::raw("Binary file, no preview available."))
+            }
+            Ok(data) => {
+                let decoded = encoding::decode(&data, data.len() as u64 >= MAX_ENTRY_PREVIEW_BYTES);
+                (format!("Preview ({})", decoded.summary()), Text::raw(decoded.text))
+            }
+            Err(err) => {
+                ("Preview".to_string(), Text::styled(format!("Cannot read archive entry: {}", err), Style::default().fg(Color::Red)))
+            }
+        }
+    });
+    let p = Paragraph::new(preview.content.clone())
+        .block(Block::default().title(preview.title.clone()).borders(Borders::ALL));
+    f.render_widget(p, area);
+}
+/// Archive format from the name, or from the content for archives that
+/// were renamed. Compressed tarballs need their name to be told apart from
+/// a single compressed file.
+fn archive_kind(path: &Path, file_type: FileType) -> Option<ArchiveKind> {
+    ArchiveKind::from_path(path).or(match file_type.mime {
+        "application/zip" => Some(ArchiveKind::Zip),
+        "application/x-tar" => Some(ArchiveKind::Tar),
+        "application/x-7z-compressed" => Some(ArchiveKind::SevenZ),
+        _ => None,
+    })
 }
 fn format_size(size: u64) -> String {
     const KB: u64 = 1024;
@@ -583,6 +2013,18 @@ fn format_size(size: u64) -> String {
         format!("{} B", size)
     }
 }
+/// Appends ` (1)`, ` (2)`, ... to `path` until it names nothing that exists.
+fn unique_path(path: &Path) -> PathBuf {
+    let mut candidate = path.to_path_buf();
+    let mut n = 1;
+    while candidate.exists() {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(" ({})", n));
+        candidate = path.with_file_name(name);
+        n += 1;
+    }
+    candidate
+}
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -599,11 +2041,189 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
             .as_ref(), )
         .split(popup_layout[1])[1]
 }
+/// stdout, unless a shell wrapper captures it (`cd "$(karu --print-cwd)"`);
+/// the TUI and programs run from it then use stderr, which is still the
+/// terminal, and stdout only ever gets the printed path.
+fn terminal_output() -> Box<dyn io::Write> {
+    if io::stdout().is_terminal() {
+        Box::new(io::stdout())
+    } else {
+        Box::new(io::stderr())
+    }
+}
+/// Hands the terminal back to the shell: cooked mode, main screen, no
+/// mouse reporting.
+fn leave_tui(terminal: &mut Tui) -> Result<()> {
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
+    terminal.show_cursor()?;
+    Ok(())
+}
+fn enter_tui(terminal: &mut Tui) -> Result<()> {
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)?;
+    // Whatever the program drew is still in ratatui's idea of the screen.
+    terminal.clear()?;
+    Ok(())
+}
+/// Runs a shell command in this terminal with the TUI suspended and waits
+/// for it to finish.
+fn run_interactive(terminal: &mut Tui, job: &InteractiveCommand) -> Result<std::process::ExitStatus> {
+    leave_tui(terminal)?;
+    // Lets prompts show that exiting returns to Karu rather than closing the terminal.
+    let level = env::var("KARU_LEVEL").ok().and_then(|level| level.parse::<u32>().ok()).unwrap_or(0) + 1;
+    let mut command = previewer::shell_command(&job.command);
+    command.current_dir(&job.dir).env("KARU_LEVEL", level.to_string());
+    if !io::stdout().is_terminal() {
+        command.stdout(io::stderr());
+    }
+    let status = command.status();
+    let prompt = match &status {
+        Ok(status) if job.after_exit == AfterExit::WaitForEnter => wait_for_enter(status),
+        _ => Ok(()),
+    };
+    // Restore the TUI before reporting anything, even a failed prompt.
+    enter_tui(terminal)?;
+    prompt?;
+    status.with_context(|| format!("Cannot run '{}'", job.command))
+}
+fn wait_for_enter(status: &std::process::ExitStatus) -> io::Result<()> {
+    let mut output = terminal_output();
+    write!(output, "\n[{}] Press Enter to return to Karu", status)?;
+    output.flush()?;
+    io::stdin().read_line(&mut String::new())?;
+    Ok(())
+}
+/// Ctrl-Z: stops the process like any other job, restoring the TUI when the
+/// shell resumes it with `fg`.
+#[cfg(unix)]
+fn suspend_process(terminal: &mut Tui) -> Result<()> {
+    leave_tui(terminal)?;
+    // SAFETY: raising a signal on our own process has no memory-safety
+    // requirements; execution continues here after SIGCONT.
+    unsafe {
+        libc::raise(libc::SIGTSTP);
+    }
+    enter_tui(terminal)
+}
+#[cfg(not(unix))]
+fn suspend_process(_terminal: &mut Tui) -> Result<()> {
+    Ok(())
+}
+/// The path exactly as the OS spells it, for output read by scripts.
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        Cow::Borrowed(path) => Cow::Borrowed(path.as_bytes()),
+        Cow::Owned(path) => Cow::Owned(path.into_bytes()),
+    }
+}
+fn handle_pager_key(app: &mut App, key: event::KeyEvent) -> Result<()> {
+    let Some(pager) = &mut app.pager else {
+        app.mode = AppMode::Normal;
+        return Ok(());
+    };
+    if let Some((prompt, input)) = &mut pager.prompt {
+        match key.code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => pager.prompt = None,
+            KeyCode::Enter => {
+                let (prompt, input) = (*prompt, std::mem::take(input));
+                pager.prompt = None;
+                match prompt {
+                    PagerPrompt::Search => pager.set_search(&input).context("Invalid search pattern")?,
+                    PagerPrompt::GotoLine => match input.trim().parse::<usize>() {
+                        Ok(line) => pager.goto_line(line),
+                        Err(_) => pager.message = Some(format!("Not a line number: {}", input)),
+                    },
+                }
+            }
+            _ => {}
+        }
+        return Ok(());
+    }
+    pager.message = None;
+    let page = pager.height as isize;
+    match key.code {
+        KeyCode::Char('q') => {
+            app.pager = None;
+            app.mode = AppMode::Normal;
+        }
+        KeyCode::Esc if pager.anchor.is_some() => pager.anchor = None,
+        KeyCode::Esc => {
+            app.pager = None;
+            app.mode = AppMode::Normal;
+        }
+        KeyCode::Down | KeyCode::Char('j') | KeyCode::Enter => pager.move_cursor(1),
+        KeyCode::Up | KeyCode::Char('k') => pager.move_cursor(-1),
+        KeyCode::PageDown | KeyCode::Char(' ') => pager.move_cursor(page),
+        KeyCode::PageUp | KeyCode::Char('b') => pager.move_cursor(-page),
+        KeyCode::Char('d') => pager.move_cursor(page / 2),
+        KeyCode::Char('u') => pager.move_cursor(-page / 2),
+        KeyCode::Home | KeyCode::Char('g') => pager.goto_line(1),
+        KeyCode::End | KeyCode::Char('G') => pager.goto_line(usize::MAX),
+        KeyCode::Left | KeyCode::Char('h') => pager.left = pager.left.saturating_sub(8),
+        KeyCode::Right | KeyCode::Char('l') if !pager.wrap => pager.left += 8,
+        KeyCode::Char('w') => {
+            pager.wrap = !pager.wrap;
+            pager.left = 0;
+        }
+        KeyCode::Char('/') => pager.prompt = Some((PagerPrompt::Search, String::new())),
+        KeyCode::Char(':') => pager.prompt = Some((PagerPrompt::GotoLine, String::new())),
+        KeyCode::Char('n') => pager.find(true, false),
+        KeyCode::Char('N') => pager.find(false, false),
+        KeyCode::Char('v') => pager.anchor = if pager.anchor.is_some() { None } else { Some(pager.cursor) },
+        KeyCode::Char('y') => {
+            let (Some((first, last)), Some(text)) = (pager.selected_range(), pager.selected_text()) else {
+                pager.message = Some("Nothing to yank".to_string());
+                return Ok(());
+            };
+            let mut output = terminal_output();
+            output.write_all(pager::osc52(&text).as_bytes())?;
+            output.flush()?;
+            pager.message = Some(if first == last {
+                format!("Yanked line {}", first + 1)
+            } else {
+                format!("Yanked lines {}-{}", first + 1, last + 1)
+            });
+            pager.anchor = None;
+        }
+        KeyCode::Char('[') => {
+            app.adjust_fold_depth(false)?;
+            app.reload_pager()?;
+        }
+        KeyCode::Char(']') => {
+            app.adjust_fold_depth(true)?;
+            app.reload_pager()?;
+        }
+        KeyCode::Char('R') => {
+            app.raw_preview = !app.raw_preview;
+            app.preview_cache = None;
+            app.reload_pager()?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
 fn run_app(
-    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,app: &mut App,
-) -> Result<()> {
+    terminal: &mut Tui,app: &mut App,
+) -> Result<Quit> {
     loop {
+        app.image.begin_frame();
         terminal.draw(|f| ui(f, app))?;
+        if let Some(cleanup) = app.image.end_frame() {
+            let backend = terminal.backend_mut();
+            io::Write::write_all(backend, cleanup.as_bytes())?;
+            io::Write::flush(backend)?;
+        }
 
         if let Some(notification_time) = app.notification_time {
             if notification_time.elapsed().as_secs() > 2 {
@@ -612,23 +2232,77 @@ fn run_app(
             }
         }
 
+        app.previewers.poll();
+        if let Some(follower) = &mut app.follow {
+            let selected = app.files.get(app.selected).map(|name| app.path.join(name));
+            if app.archive.is_some() || selected.as_ref() != Some(&follower.path) {
+                app.follow = None;
+            } else if let Err(err) = follower.poll() {
+                app.error_message = Some(format!("Stopped following {}: {}", follower.path.display(), err));
+                app.follow = None;
+            }
+        }
+        for job in app.jobs.poll() {
+            match job.result {
+                Ok(message) => {
+                    app.notification = Some(message);
+                    app.notification_time = Some(Instant::now());
+                }
+                Err(err) => app.error_message = Some(format!("{} failed: {}", job.label, err)),
+            }
+            if job.dir == app.path && app.archive.is_none() {
+                app.files = App::get_files(&app.path, app.show_hidden, app.sort)?;
+                app.selected = app.selected.min(app.files.len() - 1);
+            }
+        }
+        if let Some(output) = app.shell.poll() {
+            app.shell_output = Some(output);
+            app.shell_scroll = 0;
+            if let Err(err) = app.refresh_after_command() {
+                app.error_message = Some(err.to_string());
+            }
+        }
+
         if crossterm::event::poll(std::time::Duration::from_millis(50))? {
             if let Event::Key(key) = event::read()? {
-                // Handle universal quit key
-                if key.code == KeyCode::Char('q') {
-                    return Ok(());
+                if key.code == KeyCode::Char('z') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    suspend_process(terminal)?;
+                    continue;
+                }
+                // Handle universal quit keys, except where q and Q are typed
+                // into a prompt or mean something else (the pager closes).
+                if matches!(app.mode, AppMode::Normal | AppMode::ConfirmDelete | AppMode::Database) {
+                    match key.code {
+                        KeyCode::Char('q') => return Ok(Quit::ChangeDirectory),
+                        KeyCode::Char('Q') => return Ok(Quit::KeepDirectory),
+                        _ => {}
+                    }
                 }
 
-                if let Some(_) = &app.error_message {
+                if app.error_message.is_some() {
                     if let KeyCode::Enter | KeyCode::Esc = key.code {
                         app.error_message = None;
                     }
                     continue;
                 }
+                if app.shell_output.is_some() {
+                    match key.code {
+                        KeyCode::Enter | KeyCode::Esc => app.shell_output = None,
+                        KeyCode::Down | KeyCode::Char('j') => app.shell_scroll = app.shell_scroll.saturating_add(1),
+                        KeyCode::Up | KeyCode::Char('k') => app.shell_scroll = app.shell_scroll.saturating_sub(1),
+                        KeyCode::PageDown | KeyCode::Char(' ') => app.shell_scroll = app.shell_scroll.saturating_add(10),
+                        KeyCode::PageUp => app.shell_scroll = app.shell_scroll.saturating_sub(10),
+                        _ => {}
+                    }
+                    continue;
+                }
                 let result = match app.mode {
                     AppMode::Normal => {
                         match app.panel_focus {
                             PanelFocus::Files => match key.code {
+                                KeyCode::Enter if app.chooses_on_enter() => app.choose(),
+                                KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => app.choose(),
+                                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => app.cancel_shell_commands(),
                                 KeyCode::Down | KeyCode::Char('j') => {
                                     app.select_next();
                                     app.delayed_preview_path = Some(app.path.join(&app.files[app.selected]));
@@ -656,29 +2330,23 @@ fn run_app(
                                     app.last_highlight_time = Instant::now();
                                     result
                                 }
-                                KeyCode::Char('d') => {
-                                    app.delete_selected();
-                                    Ok(())
-                                }
+                                KeyCode::Char('d') => app.delete_selected(),
                                 KeyCode::Char('/') => {
                                     app.mode = AppMode::Editing;
                                     Ok(())
                                 }
-                                KeyCode::Char('n') => {
-                                    app.mode = AppMode::Create;
-                                    Ok(())
-                                }
+                                KeyCode::Char('n') => app.begin_input(AppMode::Create),
                                 KeyCode::Char('c') => {
                                     app.copy_selected();
                                     Ok(())
                                 }
-                                KeyCode::Char('x') => {
-                                    app.cut_selected();
-                                    Ok(())
-                                }
+                                KeyCode::Char('x') => app.cut_selected(),
                                 KeyCode::Char('v') => app.paste(),
 
-                                KeyCode::Char('o') => app.open_file(),                 KeyCode::Char('H')
+                                KeyCode::Char('o') => app.open_file(),
+                                KeyCode::Char('O') => app.begin_open_with(),
+                                KeyCode::Char('!') => app.begin_shell(),
+                                KeyCode::Char('S') => app.spawn_shell(),                 KeyCode::Char('H')
                                     if key.modifiers.contains(KeyModifiers::SHIFT) =>
                                 {
                                     app.toggle_hidden_files()
@@ -687,22 +2355,27 @@ fn run_app(
                                     app.mode = AppMode::Filter;
                                     Ok(())
                                 }
-                                KeyCode::Char('r') => {
-                                    app.mode = AppMode::Rename;
-                                    Ok(())
-                                }
-                                KeyCode::Char('+') => {
-                                    app.mode = AppMode::CreateDirectory;
-                                    Ok(())
-                                }
-                                KeyCode::Delete => {
-                                    app.delete_selected();
-                                    Ok(())
-                                }
-                                KeyCode::Char('m') => {
-                                    app.mode = AppMode::Move;
+                                KeyCode::Char('r') => app.begin_input(AppMode::Rename),
+                                KeyCode::Char('+') => app.begin_input(AppMode::CreateDirectory),
+                                KeyCode::Delete => app.delete_selected(),
+                                KeyCode::Char('m') => app.begin_input(AppMode::Move),
+                                KeyCode::Char(' ') => app.toggle_mark(),
+                                KeyCode::Char('e') => app.extract_selected(false),
+                                KeyCode::Char('E') => app.extract_selected(true),
+                                KeyCode::Char('z') => app.begin_compress(),
+                                KeyCode::Char('R') => {
+                                    app.raw_preview = !app.raw_preview;
+                                    app.preview_cache = None;
                                     Ok(())
                                 }
+                                KeyCode::Char('V') => app.open_pager(),
+                                KeyCode::Char('i') => app.edit_selected(),
+                                KeyCode::Char('p') => app.page_selected(),
+                                KeyCode::Char('F') => app.toggle_follow(),
+                                KeyCode::Char('P') => app.toggle_follow_pause(),
+                                KeyCode::Char('L') => app.begin_highlight(),
+                                KeyCode::Char('[') => app.adjust_fold_depth(false),
+                                KeyCode::Char(']') => app.adjust_fold_depth(true),
                                 KeyCode::Right | KeyCode::Char('l') => {
                                     app.panel_focus = PanelFocus::Actions;
                                     Ok(())
@@ -710,43 +2383,46 @@ fn run_app(
                                 _ => Ok(()), // Ignore other keys
                             },             PanelFocus::Actions => {
                                 match key.code {
-                                    KeyCode::Up | KeyCode::Char('k')=> {
-                                        if app.selected_action > 0 {
-                                            app.selected_action -= 1;
-                                            app.action_list_state
-                                                .select(Some(app.selected_action));
-                                        }
+                                    KeyCode::Up | KeyCode::Char('k') if app.selected_action > 0 => {
+                                        app.selected_action -= 1;
+                                        app.action_list_state
+                                            .select(Some(app.selected_action));
                                     }
-                                    KeyCode::Down | KeyCode::Char('j') => {
-                                        if app.selected_action < ACTIONS.len() - 1 {
-                                            app.selected_action += 1;
-                                            app.action_list_state
-                                                .select(Some(app.selected_action));
-                                        }
+                                    KeyCode::Down | KeyCode::Char('j') if app.selected_action < ACTIONS.len() - 1 => {
+                                        app.selected_action += 1;
+                                        app.action_list_state
+                                            .select(Some(app.selected_action));
                                     }
                                     KeyCode::Left | KeyCode::Char('h') => {
                                         app.panel_focus = PanelFocus::Files
                                     }
                                     KeyCode::Enter => {
-                                        match app.selected_action {
-                                            0 => app.cut_selected(),                             1 => app.copy_selected(),                             2 => {
-                                                if let Err(e) = app.paste() {
-                                                    app.error_message = Some(e.to_string())
-                                                }
+                                        let result = match app.selected_action {
+                                            0 => app.cut_selected(),
+                                            1 => {
+                                                app.copy_selected();
+                                                Ok(())
                                             }
-                                            3 => app.delete_selected(), 4 => app.mode = AppMode::Rename, 5 => app.mode = AppMode::Create, 6 => app.mode = AppMode::CreateDirectory, 7 => app.mode = AppMode::Move,                             8 => {
-                                                if let Err(e) = app.open_file() {
-                                                    app.error_message = Some(e.to_string())
-                                                }
-                                            }
-                                            9 => {
-                                                if let Err(e) = app.toggle_hidden_files() {
-                                                    app.error_message = Some(e.to_string())
-                                                }
-                                            }
-                                            _ => {}
+                                            2 => app.paste(),
+                                            3 => app.delete_selected(),
+                                            4 => app.begin_input(AppMode::Rename),
+                                            5 => app.begin_input(AppMode::Create),
+                                            6 => app.begin_input(AppMode::CreateDirectory),
+                                            7 => app.begin_input(AppMode::Move),
+                                            8 => app.open_file(),
+                                            9 => app.toggle_hidden_files(),
+                                            10 => app.extract_selected(false),
+                                            11 => app.extract_selected(true),
+                                            12 => app.begin_compress(),
+                                            13 => app.edit_selected(),
+                                            14 => app.begin_open_with(),
+                                            15 => app.begin_shell(),
+                                            16 => app.spawn_shell(),
+                                            _ => Ok(()),
+                                        };
+                                        if let Err(e) = result {
+                                            app.error_message = Some(e.to_string())
                                         }
-                                        app.mode = AppMode::Normal; // Return to normal mode after action
                                         app.panel_focus = PanelFocus::Files; // Return focus to files panel
                                     }
                                     KeyCode::Esc => {
@@ -779,10 +2455,13 @@ fn run_app(
                             Ok(())
                         }
                         KeyCode::Enter => {
-                            let new_path = PathBuf::from(&app.address_input);
-                            if new_path.is_dir() {
+                            // Absolute, so chosen paths and the last directory
+                            // written on quit are too.
+                            let new_path = fs::canonicalize(&app.address_input).ok().filter(|path| path.is_dir());
+                            if let Some(new_path) = new_path {
                                 app.path = new_path;
-                                app.files = App::get_files(&app.path, app.show_hidden)?;
+                                app.archive = None;
+                                app.files = App::get_files(&app.path, app.show_hidden, app.sort)?;
                                 app.selected = 0;
                             }
                             app.mode = AppMode::Normal;
@@ -809,7 +2488,7 @@ fn run_app(
                             } else {
                                 fs::File::create(new_path)?;
                             }
-                            app.files = App::get_files(&app.path, app.show_hidden)?;
+                            app.files = App::get_files(&app.path, app.show_hidden, app.sort)?;
                             app.create_input.clear();
                             app.mode = AppMode::Normal;
                             Ok(())
@@ -830,10 +2509,10 @@ fn run_app(
                             Ok(())
                         }
                         KeyCode::Enter => {
-                            let old_path = app.path.join(&app.files[app.selected].clone());
+                            let old_path = app.path.join(app.files[app.selected].clone());
                             let new_path = app.path.join(&app.rename_input);
                             fs::rename(&old_path, &new_path)?;
-                            app.files = App::get_files(&app.path, app.show_hidden)?;
+                            app.files = App::get_files(&app.path, app.show_hidden, app.sort)?;
                             app.notification = Some(format!(
                                 "Renamed '{}' to '{}'",                 old_path.file_name().unwrap().to_str().unwrap(),                 new_path.file_name().unwrap().to_str().unwrap()
                             ));
@@ -858,7 +2537,7 @@ fn run_app(
                             Ok(())
                         }
                         KeyCode::Enter => {
-                            app.files = App::get_files(&app.path, app.show_hidden)?;
+                            app.refresh_files()?;
                             app.files.retain(|f| f.contains(&app.filter_input));
                             app.selected = 0;
                             app.mode = AppMode::Normal;
@@ -866,7 +2545,7 @@ fn run_app(
                         }
                         KeyCode::Esc => {
                             app.filter_input.clear();
-                            app.files = App::get_files(&app.path, app.show_hidden)?;
+                            app.refresh_files()?;
                             app.mode = AppMode::Normal;
                             Ok(())
                         }
@@ -883,7 +2562,7 @@ fn run_app(
                         KeyCode::Enter => {
                             let new_path = app.path.join(&app.create_directory_input);
                             fs::create_dir_all(new_path)?;
-                            app.files = App::get_files(&app.path, app.show_hidden)?;
+                            app.files = App::get_files(&app.path, app.show_hidden, app.sort)?;
                             app.create_directory_input.clear();
                             app.mode = AppMode::Normal;
                             Ok(())
@@ -907,7 +2586,7 @@ fn run_app(
                             let old_path = app.path.join(&app.files[app.selected]);
                             let new_path = PathBuf::from(&app.move_input);
                             fs::rename(old_path, new_path)?;
-                            app.files = App::get_files(&app.path, app.show_hidden)?;
+                            app.files = App::get_files(&app.path, app.show_hidden, app.sort)?;
                             app.move_input.clear();
                             app.mode = AppMode::Normal;
                             Ok(())
@@ -917,29 +2596,185 @@ fn run_app(
                             app.mode = AppMode::Normal;
                             Ok(())
                         }
-                        _ => Ok(()),     }, };
+                        _ => Ok(()),     },
+                    AppMode::Database => {
+                        if let Some(view) = &mut app.database {
+                            match key.code {
+                                KeyCode::Down | KeyCode::Char('j') => view.select(view.selected + 1),
+                                KeyCode::Up | KeyCode::Char('k') => view.select(view.selected.saturating_sub(1)),
+                                KeyCode::Esc | KeyCode::Left | KeyCode::Char('h') => {
+                                    app.database = None;
+                                    app.mode = AppMode::Normal;
+                                }
+                                _ => {}
+                            }
+                        }
+                        Ok(())
+                    }
+                    AppMode::Pager => handle_pager_key(app, key),
+                    AppMode::Highlight => match key.code {
+                        KeyCode::Char(c) => {
+                            app.highlight_input.push(c);
+                            Ok(())
+                        }
+                        KeyCode::Backspace => {
+                            app.highlight_input.pop();
+                            Ok(())
+                        }
+                        KeyCode::Enter => app.apply_highlight(),
+                        KeyCode::Esc => {
+                            app.mode = AppMode::Normal;
+                            Ok(())
+                        }
+                        _ => Ok(()),
+                    },
+                    AppMode::Shell => match key.code {
+                        KeyCode::Char(c) => {
+                            app.shell_input.push(c);
+                            Ok(())
+                        }
+                        KeyCode::Backspace => {
+                            app.shell_input.pop();
+                            Ok(())
+                        }
+                        KeyCode::Tab => {
+                            app.shell_in_terminal = !app.shell_in_terminal;
+                            Ok(())
+                        }
+                        KeyCode::Enter => app.run_shell_command(),
+                        KeyCode::Esc => {
+                            app.shell_input.clear();
+                            app.mode = AppMode::Normal;
+                            Ok(())
+                        }
+                        _ => Ok(()),
+                    },
+                    AppMode::OpenWith => match key.code {
+                        KeyCode::Up | KeyCode::BackTab => {
+                            let count = app.open_with.len() + 1;
+                            app.open_with_selected = (app.open_with_selected + count - 1) % count;
+                            Ok(())
+                        }
+                        KeyCode::Down | KeyCode::Tab => {
+                            app.open_with_selected = (app.open_with_selected + 1) % (app.open_with.len() + 1);
+                            Ok(())
+                        }
+                        KeyCode::Char(c) => {
+                            app.open_with_input.push(c);
+                            Ok(())
+                        }
+                        KeyCode::Backspace => {
+                            app.open_with_input.pop();
+                            Ok(())
+                        }
+                        KeyCode::Enter => app.confirm_open_with(),
+                        KeyCode::Esc => {
+                            app.mode = AppMode::Normal;
+                            Ok(())
+                        }
+                        _ => Ok(()),
+                    },
+                    AppMode::Compress => match key.code {
+                        KeyCode::Char(c) => {
+                            app.compress_input.push(c);
+                            Ok(())
+                        }
+                        KeyCode::Backspace => {
+                            app.compress_input.pop();
+                            Ok(())
+                        }
+                        KeyCode::Tab => {
+                            app.compress_format = (app.compress_format + 1) % COMPRESS_FORMATS.len();
+                            Ok(())
+                        }
+                        KeyCode::Enter => app.compress_selected(),
+                        KeyCode::Esc => {
+                            app.compress_input.clear();
+                            app.mode = AppMode::Normal;
+                            Ok(())
+                        }
+                        _ => Ok(()),
+                    }, };
                 if let Err(e) = result {
                     app.error_message = Some(e.to_string());
                 }
+                if let Some(paths) = app.chosen.take() {
+                    return Ok(Quit::Chose(paths));
+                }
+                if let Some(job) = app.interactive.take() {
+                    match run_interactive(terminal, &job) {
+                        Ok(status) if !status.success() && job.after_exit == AfterExit::ReportFailure => {
+                            app.error_message = Some(format!("'{}' exited with {}", job.command, status))
+                        }
+                        Ok(_) => {}
+                        Err(err) => app.error_message = Some(format!("{:#}", err)),
+                    }
+                    if let Err(err) = app.refresh_after_command() {
+                        app.error_message = Some(err.to_string());
+                    }
+                }
             }
         }
     }
 }
-fn main() -> Result<()> {
+fn main() -> Result<ExitCode> {
+    let args = match cli::parse() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("karu: {:#}\nTry 'karu --help' for more information.", err);
+            return Ok(ExitCode::from(2));
+        }
+    };
+    panics::install_panic_hook();
+    // Before the TUI starts, so a bad path is reported on the plain terminal.
+    let mut app = match App::from_args(&args) {
+        Ok(app) => app,
+        Err(err) => {
+            eprintln!("karu: {:#}", err);
+            return Ok(ExitCode::from(2));
+        }
+    };
     enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    let mut output = terminal_output();
+    execute!(output, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(output);
     let mut terminal = Terminal::new(backend)?;
-    let mut app = App::new(env::current_dir()?)?;
     let res = run_app(&mut terminal, &mut app);
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
-    if let Err(err) = res {
-        println!("{err:?}");
+    leave_tui(&mut terminal)?;
+    if let Some(chooser) = &args.choose {
+        let Ok(Quit::Chose(paths)) = res else {
+            if let Err(err) = res {
+                eprintln!("{err:?}");
+            }
+            // Cancelled, so scripts can tell nothing was chosen.
+            return Ok(ExitCode::FAILURE);
+        };
+        let separator = if args.null { b'\0' } else { b'\n' };
+        let mut output = Vec::new();
+        for path in &paths {
+            output.extend_from_slice(&path_bytes(path));
+            output.push(separator);
+        }
+        match &chooser.output {
+            Some(file) => fs::write(file, output).with_context(|| format!("Cannot write {}", file.display()))?,
+            None => io::Write::write_all(&mut io::stdout(), &output)?,
+        }
+        return Ok(ExitCode::SUCCESS);
     }
-    Ok(())
+    match res {
+        Ok(Quit::ChangeDirectory) => {
+            if let Some(cwd_file) = &args.cwd_file {
+                fs::write(cwd_file, path_bytes(&app.path))
+                    .with_context(|| format!("Cannot write {}", cwd_file.display()))?;
+            }
+            if args.print_cwd {
+                let mut line = path_bytes(&app.path).into_owned();
+                line.push(b'\n');
+                io::Write::write_all(&mut io::stdout(), &line)?;
+            }
+        }
+        Ok(Quit::KeepDirectory | Quit::Chose(_)) => {}
+        Err(err) => eprintln!("{err:?}"),
+    }
+    Ok(ExitCode::SUCCESS)
 }
@@ -0,0 +1,348 @@
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use std::sync::OnceLock;
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::SyntaxSet,
+};
+
+/// Loading the bundled syntaxes takes a noticeable moment, so it happens once.
+fn highlighting() -> &'static (SyntaxSet, Theme) {
+    static HIGHLIGHTING: OnceLock<(SyntaxSet, Theme)> = OnceLock::new();
+    HIGHLIGHTING.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults();
+        let theme = themes.themes.remove("base16-ocean.dark").unwrap_or_default();
+        (SyntaxSet::load_defaults_newlines(), theme)
+    })
+}
+
+/// Renders Markdown source into styled lines `width` columns wide.
+pub fn render(source: &str, width: usize) -> Vec<Line<'static>> {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut renderer = Renderer { width, ..Renderer::default() };
+    for event in Parser::new_ext(source, options) {
+        renderer.event(event);
+    }
+    renderer.flush_line();
+    while renderer.lines.last().is_some_and(|line| line.width() == 0) {
+        renderer.lines.pop();
+    }
+    renderer.lines
+}
+
+#[derive(Default)]
+struct Renderer {
+    lines: Vec<Line<'static>>,
+    spans: Vec<Span<'static>>,
+    styles: Vec<Style>,
+    quote_depth: usize,
+    /// One entry per open list: the next number, or `None` for bullets.
+    lists: Vec<Option<u64>>,
+    pending_marker: Option<String>,
+    code: Option<(String, String)>,
+    table: Option<TableState>,
+    link_target: Option<String>,
+    width: usize,
+}
+
+#[derive(Default)]
+struct TableState {
+    alignments: Vec<Alignment>,rows: Vec<Vec<String>>,header_rows: usize,
+}
+
+impl Renderer {
+    fn style(&self) -> Style {
+        self.styles.last().copied().unwrap_or_default()
+    }
+    fn push_style(&mut self, apply: impl FnOnce(Style) -> Style) {
+        self.styles.push(apply(self.style()));
+    }
+    fn prefix(&mut self) -> Vec<Span<'static>> {
+        let mut prefix = Vec::new();
+        if self.quote_depth > 0 {
+            prefix.push(Span::styled("│ ".repeat(self.quote_depth), Style::default().fg(Color::DarkGray)));
+        }
+        if !self.lists.is_empty() {
+            prefix.push(Span::raw("   ".repeat(self.lists.len() - 1)));
+            let marker = self.pending_marker.take().unwrap_or_default();
+            prefix.push(Span::styled(format!("{:<3}", marker), Style::default().fg(Color::Yellow)));
+        }
+        prefix
+    }
+    fn flush_line(&mut self) {
+        if self.spans.is_empty() && self.pending_marker.is_none() {
+            return;
+        }
+        let mut spans = self.prefix();
+        spans.append(&mut self.spans);
+        self.lines.push(Line::from(spans));
+    }
+    fn blank_line(&mut self) {
+        self.flush_line();
+        if self.lines.last().is_some_and(|line| line.width() > 0) {
+            self.lines.push(Line::raw(""));
+        }
+    }
+    fn event(&mut self, event: Event) {
+        if let Some(table) = &mut self.table {
+            match event {
+                Event::Text(text) | Event::Code(text) => {
+                    if let Some(cell) = table.rows.last_mut().and_then(|row| row.last_mut()) {
+                        cell.push_str(&text);
+                    }
+                    return;
+                }
+                Event::Start(Tag::TableHead) | Event::Start(Tag::TableRow) => {
+                    table.rows.push(Vec::new());
+                    return;
+                }
+                Event::End(TagEnd::TableHead) => {
+                    table.header_rows = table.rows.len();
+                    return;
+                }
+                Event::Start(Tag::TableCell) => {
+                    if let Some(row) = table.rows.last_mut() {
+                        row.push(String::new());
+                    }
+                    return;
+                }
+                Event::End(TagEnd::Table) => {}
+                _ => return,
+            }
+        }
+        if let Some((_, code)) = &mut self.code {
+            if let Event::Text(text) = &event {
+                code.push_str(text);
+                return;
+            }
+        }
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => self.spans.push(Span::styled(text.into_string(), self.style())),
+            Event::Code(code) => {
+                self.spans.push(Span::styled(code.into_string(), Style::default().fg(Color::Yellow).bg(Color::Rgb(40, 40, 40))))
+            }
+            Event::InlineMath(math) | Event::DisplayMath(math) => {
+                self.spans.push(Span::styled(math.into_string(), Style::default().fg(Color::Magenta)))
+            }
+            Event::Html(html) | Event::InlineHtml(html) => {
+                for (index, part) in html.lines().enumerate() {
+                    if index > 0 {
+                        self.flush_line();
+                    }
+                    self.spans.push(Span::styled(part.to_string(), Style::default().fg(Color::DarkGray)));
+                }
+            }
+            Event::FootnoteReference(name) => {
+                self.spans.push(Span::styled(format!("[^{}]", name), Style::default().fg(Color::Blue)))
+            }
+            Event::SoftBreak => self.spans.push(Span::raw(" ")),
+            Event::HardBreak => self.flush_line(),
+            Event::Rule => {
+                self.flush_line();
+                self.lines.push(Line::styled("─".repeat(self.width), Style::default().fg(Color::DarkGray)));
+                self.blank_line();
+            }
+            Event::TaskListMarker(checked) => {
+                let (marker, color) = if checked { ("[x] ", Color::Green) } else { ("[ ] ", Color::DarkGray) };
+                self.spans.push(Span::styled(marker, Style::default().fg(color)));
+            }
+        }
+    }
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Heading { level, .. } => {
+                self.flush_line();
+                let style = match level {
+                    HeadingLevel::H1 => Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                    HeadingLevel::H2 => Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+                    _ => Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD),
+                };
+                self.styles.push(style);
+                let hashes = "#".repeat(level as usize);
+                self.spans.push(Span::styled(format!("{} ", hashes), Style::default().fg(Color::DarkGray)));
+            }
+            Tag::BlockQuote(_) => {
+                self.flush_line();
+                self.quote_depth += 1;
+                self.push_style(|style| style.add_modifier(Modifier::ITALIC));
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush_line();
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info.split([',', ' ']).next().unwrap_or_default().to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some((lang, String::new()));
+            }
+            Tag::List(start) => {
+                self.flush_line();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush_line();
+                self.pending_marker = Some(match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}.", *number - 1)
+                    }
+                    _ => "•".to_string(),
+                });
+            }
+            Tag::Emphasis => self.push_style(|style| style.add_modifier(Modifier::ITALIC)),
+            Tag::Strong => self.push_style(|style| style.add_modifier(Modifier::BOLD)),
+            Tag::Strikethrough => self.push_style(|style| style.add_modifier(Modifier::CROSSED_OUT)),
+            Tag::Link { dest_url, .. } => {
+                self.link_target = Some(dest_url.into_string());
+                self.push_style(|style| style.fg(Color::Blue).add_modifier(Modifier::UNDERLINED));
+            }
+            Tag::Image { .. } => {
+                self.push_style(|_| Style::default().fg(Color::Magenta));
+                self.spans.push(Span::styled("[image: ", self.style()));
+            }
+            Tag::Table(alignments) => {
+                self.flush_line();
+                self.table = Some(TableState { alignments, ..TableState::default() });
+            }
+            Tag::FootnoteDefinition(name) => {
+                self.flush_line();
+                self.spans.push(Span::styled(format!("[^{}]: ", name), Style::default().fg(Color::Blue)));
+            }
+            _ => {}
+        }
+    }
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => {
+                if self.lists.is_empty() {
+                    self.blank_line();
+                } else {
+                    self.flush_line();
+                }
+            }
+            TagEnd::Heading(_) => {
+                self.styles.pop();
+                self.blank_line();
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush_line();
+                self.styles.pop();
+                self.quote_depth -= 1;
+                self.blank_line();
+            }
+            TagEnd::CodeBlock => {
+                if let Some((lang, code)) = self.code.take() {
+                    self.code_block(&lang, &code);
+                }
+                self.blank_line();
+            }
+            TagEnd::List(_) => {
+                self.flush_line();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank_line();
+                }
+            }
+            TagEnd::Item => self.flush_line(),
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => {
+                self.styles.pop();
+            }
+            TagEnd::Link => {
+                self.styles.pop();
+                // Show where a link goes unless its text already says it.
+                if let Some(target) = self.link_target.take() {
+                    let text_is_target = self.spans.last().is_some_and(|span| span.content == target.as_str());
+                    if !text_is_target && !target.starts_with('#') {
+                        self.spans.push(Span::styled(format!(" <{}>", target), Style::default().fg(Color::DarkGray)));
+                    }
+                }
+            }
+            TagEnd::Image => {
+                self.spans.push(Span::styled("]", self.style()));
+                self.styles.pop();
+            }
+            TagEnd::Table => {
+                if let Some(table) = self.table.take() {
+                    self.render_table(table);
+                }
+                self.blank_line();
+            }
+            TagEnd::FootnoteDefinition => self.blank_line(),
+            _ => {}
+        }
+    }
+    fn code_block(&mut self, lang: &str, code: &str) {
+        let (syntaxes, theme) = highlighting();
+        let syntax = syntaxes
+            .find_syntax_by_token(lang)
+            .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+        let mut highlighter = HighlightLines::new(syntax, theme);
+        let bar = Span::styled("▎ ", Style::default().fg(Color::DarkGray));
+        for line in code.lines() {
+            self.spans.push(bar.clone());
+            // Keep the newline so syntaxes that match on it stay in sync.
+            let with_newline = format!("{}\n", line);
+            match highlighter.highlight_line(&with_newline, syntaxes) {
+                Ok(ranges) => {
+                    for (style, text) in ranges {
+                        let fg = style.foreground;
+                        let text = text.trim_end_matches('\n');
+                        if !text.is_empty() {
+                            self.spans.push(Span::styled(text.to_string(), Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b))));
+                        }
+                    }
+                }
+                Err(_) => self.spans.push(Span::raw(line.to_string())),
+            }
+            self.flush_line();
+        }
+    }
+    fn render_table(&mut self, table: TableState) {
+        const MAX_COLUMN_WIDTH: usize = 30;
+        let columns = table.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                table.rows
+                    .iter()
+                    .filter_map(|row| row.get(column))
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    .clamp(1, MAX_COLUMN_WIDTH)
+            })
+            .collect();
+        let border = Style::default().fg(Color::DarkGray);
+        for (index, row) in table.rows.iter().enumerate() {
+            let is_header = index < table.header_rows;
+            let style = if is_header { Style::default().add_modifier(Modifier::BOLD) } else { Style::default() };
+            for (column, width) in widths.iter().enumerate() {
+                if column > 0 {
+                    self.spans.push(Span::styled(" │ ", border));
+                }
+                let cell = row.get(column).map(String::as_str).unwrap_or_default();
+                let cell: String = if cell.chars().count() > *width {
+                    cell.chars().take(width.saturating_sub(1)).chain(['…']).collect()
+                } else {
+                    cell.to_string()
+                };
+                let text = match table.alignments.get(column) {
+                    Some(Alignment::Right) => format!("{:>width$}", cell, width = width),
+                    Some(Alignment::Center) => format!("{:^width$}", cell, width = width),
+                    _ => format!("{:<width$}", cell, width = width),
+                };
+                self.spans.push(Span::styled(text, style));
+            }
+            self.flush_line();
+            if is_header && index + 1 == table.header_rows {
+                let separator: Vec<String> = widths.iter().map(|width| "─".repeat(*width)).collect();
+                self.spans.push(Span::styled(separator.join("─┼─"), border));
+                self.flush_line();
+            }
+        }
+    }
+}
@@ -0,0 +1,417 @@
+use anyhow::Result;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use symphonia::core::{
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::{MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey},
+    probe::Hint,
+};
+
+/// What a media file holds: label/value pairs for the preview and the
+/// embedded cover art, if any, still encoded as JPEG or PNG.
+pub struct MediaInfo {
+    pub fields: Vec<(&'static str, String)>,pub cover: Option<Vec<u8>>,
+}
+
+struct Track {
+    kind: &'static str,description: String,
+}
+
+pub fn probe(path: &Path, mime: &str) -> Result<MediaInfo> {
+    let size = fs::metadata(path)?.len();
+    let mut fields = vec![("Format", format_name(mime, path))];
+    let mut tracks = Vec::new();
+    let mut duration = None;
+    let mut tags = Vec::new();
+    let mut cover = None;
+    match crate::panics::quietly(|| read_audio(path)) {
+        Ok(audio) => {
+            tracks.extend(audio.tracks);
+            duration = audio.duration;
+            tags = audio.tags;
+            cover = audio.cover;
+        }
+        // Video-only files have nothing symphonia can read; the container
+        // parsers below still describe them.
+        Err(err) if !mime.starts_with("video/") => return Err(err),
+        Err(_) => {}
+    }
+    let video = match mime {
+        "video/mp4" | "video/quicktime" | "audio/m4a" | "video/x-m4v" => mp4_video(path).unwrap_or_default(),
+        "video/x-matroska" | "video/webm" => matroska_video(path).unwrap_or_default(),
+        _ => ContainerInfo::default(),
+    };
+    duration = duration.or(video.duration);
+    tracks.splice(0..0, video.tracks);
+    if let Some(seconds) = duration.filter(|seconds| *seconds > 0.0) {
+        fields.push(("Duration", format_duration(seconds)));
+        fields.push(("Bitrate", format!("{} kbps", (size as f64 * 8.0 / seconds / 1000.0).round())));
+    }
+    for track in tracks {
+        fields.push((track.kind, track.description));
+    }
+    fields.extend(tags);
+    Ok(MediaInfo { fields, cover })
+}
+
+fn format_name(mime: &str, path: &Path) -> String {
+    let name = match mime {
+        "audio/mpeg" => "MP3",
+        "audio/x-flac" => "FLAC",
+        "audio/ogg" => "Ogg",
+        "audio/x-wav" => "WAV",
+        "audio/aac" => "AAC",
+        "audio/x-aiff" => "AIFF",
+        "audio/m4a" | "video/mp4" | "video/x-m4v" => "MP4",
+        "video/quicktime" => "QuickTime",
+        "video/x-matroska" => "Matroska",
+        "video/webm" => "WebM",
+        _ => return path.extension().map_or(mime.to_string(), |ext| ext.to_string_lossy().to_uppercase()),
+    };
+    name.to_string()
+}
+
+fn format_duration(seconds: f64) -> String {
+    if seconds < 60.0 {
+        return format!("{:.1} s", seconds);
+    }
+    let total = seconds.round() as u64;
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+struct AudioInfo {
+    tracks: Vec<Track>,duration: Option<f64>,tags: Vec<(&'static str, String)>,cover: Option<Vec<u8>>,
+}
+
+/// Audio tracks, tags (ID3, Vorbis comments, MP4 atoms) and cover art.
+fn read_audio(path: &Path) -> Result<AudioInfo> {
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+    let source = MediaSourceStream::new(Box::new(fs::File::open(path)?), Default::default());
+    let mut probed = symphonia::default::get_probe().format(&hint, source, &FormatOptions::default(), &MetadataOptions::default())?;
+    let codecs = symphonia::default::get_codecs();
+    let mut tracks = Vec::new();
+    let mut duration: Option<f64> = None;
+    for track in probed.format.tracks() {
+        let params = &track.codec_params;
+        let Some(codec) = codecs.get_codec(params.codec) else {
+            continue;
+        };
+        let mut parts = vec![codec.short_name.to_uppercase()];
+        parts.extend(params.sample_rate.map(|rate| format!("{} Hz", rate)));
+        parts.extend(params.channels.map(|channels| match channels.count() {
+            1 => "mono".to_string(),
+            2 => "stereo".to_string(),
+            count => format!("{} channels", count),
+        }));
+        parts.extend(params.bits_per_sample.map(|bits| format!("{}-bit", bits)));
+        tracks.push(Track { kind: "Audio", description: parts.join(", ") });
+        if let (Some(frames), Some(time_base)) = (params.n_frames, params.time_base) {
+            let time = time_base.calc_time(frames);
+            let seconds = time.seconds as f64 + time.frac;
+            duration = Some(duration.map_or(seconds, |longest| longest.max(seconds)));
+        }
+    }
+    // Tags can sit before the container (ID3v2 on MP3) or inside it.
+    let mut tags = Vec::new();
+    let mut cover = None;
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|metadata| metadata.current()) {
+        collect_tags(revision, &mut tags, &mut cover);
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        collect_tags(revision, &mut tags, &mut cover);
+    }
+    Ok(AudioInfo { tracks, duration, tags, cover })
+}
+
+fn collect_tags(revision: &MetadataRevision, tags: &mut Vec<(&'static str, String)>, cover: &mut Option<Vec<u8>>) {
+    for tag in revision.tags() {
+        let label = match tag.std_key {
+            Some(StandardTagKey::TrackTitle) => "Title",
+            Some(StandardTagKey::Artist) => "Artist",
+            Some(StandardTagKey::Album) => "Album",
+            Some(StandardTagKey::AlbumArtist) => "Album artist",
+            Some(StandardTagKey::Composer) => "Composer",
+            Some(StandardTagKey::Genre) => "Genre",
+            Some(StandardTagKey::Date | StandardTagKey::ReleaseDate) => "Date",
+            Some(StandardTagKey::TrackNumber) => "Track",
+            Some(StandardTagKey::DiscNumber) => "Disc",
+            Some(StandardTagKey::Comment) => "Comment",
+            Some(StandardTagKey::Encoder) => "Encoder",
+            _ => continue,
+        };
+        let value = tag.value.to_string().trim_matches(|c: char| c.is_whitespace() || c == '\0').to_string();
+        if !value.is_empty() && !tags.iter().any(|(existing, _)| *existing == label) {
+            tags.push((label, value));
+        }
+    }
+    let front = revision.visuals().iter().find(|visual| visual.usage == Some(StandardVisualKey::FrontCover));
+    if let Some(visual) = front.or_else(|| revision.visuals().first()) {
+        if cover.is_none() || visual.usage == Some(StandardVisualKey::FrontCover) {
+            *cover = Some(visual.data.to_vec());
+        }
+    }
+}
+
+#[derive(Default)]
+struct ContainerInfo {
+    tracks: Vec<Track>,duration: Option<f64>,
+}
+
+fn video_codec_name(code: &str) -> String {
+    let name = match code {
+        "avc1" | "avc3" | "V_MPEG4/ISO/AVC" => "H.264",
+        "hvc1" | "hev1" | "V_MPEGH/ISO/HEVC" => "HEVC",
+        "av01" | "V_AV1" => "AV1",
+        "vp09" | "V_VP9" => "VP9",
+        "vp08" | "V_VP8" => "VP8",
+        "mp4v" | "V_MPEG4/ISO/ASP" => "MPEG-4",
+        "apcn" | "apch" | "apcs" | "apco" | "ap4h" => "ProRes",
+        other => other.strip_prefix("V_").unwrap_or(other),
+    };
+    name.to_string()
+}
+
+/// Iterates over the boxes in an MP4 box payload as `(type, payload)`.
+fn mp4_boxes(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        if data.len() < 8 {
+            return None;
+        }
+        let kind: [u8; 4] = data[4..8].try_into().ok()?;
+        let (header, size) = match u32::from_be_bytes(data[0..4].try_into().ok()?) {
+            0 => (8, data.len()),
+            1 => (16, u64::from_be_bytes(data.get(8..16)?.try_into().ok()?) as usize),
+            size => (8, size as usize),
+        };
+        if size < header || size > data.len() {
+            return None;
+        }
+        let payload = &data[header..size];
+        data = &data[size..];
+        Some((kind, payload))
+    })
+}
+fn mp4_child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    mp4_boxes(data).find(|(k, _)| k == kind).map(|(_, payload)| payload)
+}
+
+/// Video tracks and duration from the `moov` box, which may sit at either
+/// end of the file.
+fn mp4_video(path: &Path) -> Result<ContainerInfo> {
+    const MAX_MOOV_BYTES: u64 = 32 * 1024 * 1024;
+    let mut file = fs::File::open(path)?;
+    let length = file.metadata()?.len();
+    let mut offset = 0;
+    let mut moov = None;
+    while offset + 8 <= length {
+        let mut header = [0; 16];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header[..8])?;
+        let (header_size, size) = match u32::from_be_bytes(header[0..4].try_into()?) {
+            0 => (8, length - offset),
+            1 => {
+                file.read_exact(&mut header[8..16])?;
+                (16, u64::from_be_bytes(header[8..16].try_into()?))
+            }
+            size => (8, size as u64),
+        };
+        if size < header_size || size > length - offset {
+            break;
+        }
+        if &header[4..8] == b"moov" {
+            let mut data = Vec::new();
+            file.take((size - header_size).min(MAX_MOOV_BYTES)).read_to_end(&mut data)?;
+            moov = Some(data);
+            break;
+        }
+        let Some(next) = offset.checked_add(size) else {
+            break;
+        };
+        offset = next;
+    }
+    let mut info = ContainerInfo::default();
+    let Some(moov) = moov else {
+        return Ok(info);
+    };
+    if let Some(mvhd) = mp4_child(&moov, b"mvhd") {
+        let (scale, duration) = match mvhd.first() {
+            Some(1) if mvhd.len() >= 32 => (
+                u32::from_be_bytes(mvhd[20..24].try_into()?) as f64,
+                u64::from_be_bytes(mvhd[24..32].try_into()?) as f64,
+            ),
+            Some(0) if mvhd.len() >= 20 => (
+                u32::from_be_bytes(mvhd[12..16].try_into()?) as f64,
+                u32::from_be_bytes(mvhd[16..20].try_into()?) as f64,
+            ),
+            _ => (0.0, 0.0),
+        };
+        info.duration = (scale > 0.0).then_some(duration / scale);
+    }
+    for (kind, trak) in mp4_boxes(&moov) {
+        if &kind != b"trak" {
+            continue;
+        }
+        let Some(mdia) = mp4_child(trak, b"mdia") else {
+            continue;
+        };
+        if mp4_child(mdia, b"hdlr").and_then(|hdlr| hdlr.get(8..12)) != Some(b"vide") {
+            continue;
+        }
+        let codec = mp4_child(mdia, b"minf")
+            .and_then(|minf| mp4_child(minf, b"stbl"))
+            .and_then(|stbl| mp4_child(stbl, b"stsd"))
+            .and_then(|stsd| stsd.get(12..16))
+            .map(|code| video_codec_name(&String::from_utf8_lossy(code)));
+        // Width and height are 16.16 fixed point at the end of `tkhd`.
+        let size = mp4_child(trak, b"tkhd").filter(|tkhd| tkhd.len() >= 8).map(|tkhd| {
+            let end = tkhd.len();
+            let width = u32::from_be_bytes(tkhd[end - 8..end - 4].try_into().unwrap_or_default()) >> 16;
+            let height = u32::from_be_bytes(tkhd[end - 4..end].try_into().unwrap_or_default()) >> 16;
+            format!("{} x {}", width, height)
+        });
+        let description: Vec<String> = codec.into_iter().chain(size).collect();
+        info.tracks.push(Track { kind: "Video", description: description.join(", ") });
+    }
+    Ok(info)
+}
+
+/// Reads an EBML variable-length integer, keeping the length marker for
+/// element IDs and stripping it for sizes. `None` in the value means an
+/// unknown size.
+fn ebml_vint(data: &[u8], keep_marker: bool) -> Option<(Option<u64>, usize)> {
+    let first = *data.first()?;
+    let length = first.leading_zeros() as usize + 1;
+    if length > 8 || data.len() < length {
+        return None;
+    }
+    let mut value = if keep_marker { first as u64 } else { (first as u64) & (0xFF >> length) };
+    for byte in &data[1..length] {
+        value = (value << 8) | *byte as u64;
+    }
+    let all_ones = !keep_marker && value == (1u64 << (7 * length)) - 1;
+    Some(((!all_ones).then_some(value), length))
+}
+/// Iterates over the elements in an EBML payload as `(id, payload)`. An
+/// element of unknown size runs to the end of the data.
+fn ebml_elements(mut data: &[u8]) -> impl Iterator<Item = (u64, &[u8])> {
+    std::iter::from_fn(move || {
+        let (id, id_length) = ebml_vint(data, true)?;
+        let (size, size_length) = ebml_vint(&data[id_length..], false)?;
+        let start = id_length + size_length;
+        let end = size.map_or(data.len(), |size| (start + size as usize).min(data.len()));
+        let payload = &data[start..end];
+        data = &data[end..];
+        Some((id?, payload))
+    })
+}
+fn ebml_uint(data: &[u8]) -> u64 {
+    data.iter().take(8).fold(0, |value, byte| (value << 8) | *byte as u64)
+}
+fn ebml_float(data: &[u8]) -> Option<f64> {
+    match data.len() {
+        4 => Some(f32::from_be_bytes(data.try_into().ok()?) as f64),
+        8 => Some(f64::from_be_bytes(data.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+/// Video tracks and duration from the segment header of a Matroska or WebM
+/// file, which comes before the first cluster.
+fn matroska_video(path: &Path) -> Result<ContainerInfo> {
+    const SEGMENT: u64 = 0x18538067;
+    const INFO: u64 = 0x1549A966;
+    const TIMESTAMP_SCALE: u64 = 0x2AD7B1;
+    const DURATION: u64 = 0x4489;
+    const TRACKS: u64 = 0x1654AE6B;
+    const TRACK_ENTRY: u64 = 0xAE;
+    const TRACK_TYPE: u64 = 0x83;
+    const CODEC_ID: u64 = 0x86;
+    const VIDEO: u64 = 0xE0;
+    const PIXEL_WIDTH: u64 = 0xB0;
+    const PIXEL_HEIGHT: u64 = 0xBA;
+    const CLUSTER: u64 = 0x1F43B675;
+    let mut head = Vec::new();
+    fs::File::open(path)?.take(1024 * 1024).read_to_end(&mut head)?;
+    let mut info = ContainerInfo::default();
+    let Some((_, segment)) = ebml_elements(&head).find(|(id, _)| *id == SEGMENT) else {
+        return Ok(info);
+    };
+    for (id, payload) in ebml_elements(segment) {
+        match id {
+            INFO => {
+                let mut scale = 1_000_000.0;
+                let mut duration = None;
+                for (id, value) in ebml_elements(payload) {
+                    match id {
+                        TIMESTAMP_SCALE => scale = ebml_uint(value) as f64,
+                        DURATION => duration = ebml_float(value),
+                        _ => {}
+                    }
+                }
+                info.duration = duration.map(|duration| duration * scale / 1e9);
+            }
+            TRACKS => {
+                for (_, entry) in ebml_elements(payload).filter(|(id, _)| *id == TRACK_ENTRY) {
+                    let mut is_video = false;
+                    let mut codec = None;
+                    let mut size = None;
+                    for (id, value) in ebml_elements(entry) {
+                        match id {
+                            TRACK_TYPE => is_video = ebml_uint(value) == 1,
+                            CODEC_ID => codec = Some(video_codec_name(String::from_utf8_lossy(value).trim_end_matches('\0'))),
+                            VIDEO => {
+                                let dimension = |wanted| ebml_elements(value).find(|(id, _)| *id == wanted).map(|(_, v)| ebml_uint(v));
+                                if let (Some(width), Some(height)) = (dimension(PIXEL_WIDTH), dimension(PIXEL_HEIGHT)) {
+                                    size = Some(format!("{} x {}", width, height));
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    if is_video {
+                        let description: Vec<String> = codec.into_iter().chain(size).collect();
+                        info.tracks.push(Track { kind: "Video", description: description.join(", ") });
+                    }
+                }
+            }
+            CLUSTER => break,
+            _ => {}
+        }
+    }
+    Ok(info)
+}
+
+/// Probed media files, re-read when a file changes.
+#[derive(Default)]
+pub struct MediaCache {
+    entries: HashMap<PathBuf, (Option<SystemTime>, Result<MediaInfo, String>)>,
+}
+impl MediaCache {
+    pub fn get(&mut self, path: &Path, mime: &str) -> &Result<MediaInfo, String> {
+        const MAX_ENTRIES: usize = 16;
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        let fresh = matches!(self.entries.get(path), Some((cached, _)) if *cached == modified);
+        if !fresh {
+            if self.entries.len() >= MAX_ENTRIES {
+                self.entries.clear();
+            }
+            let info = probe(path, mime).map_err(|err| format!("{:#}", err));
+            self.entries.insert(path.to_path_buf(), (modified, info));
+        }
+        &self.entries[path].1
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{
+    config::OpenerConfig,
+    previewer::{self, FileMatcher},
+};
+use anyhow::{Context, Result};
+use std::{path::Path, process::Stdio, thread};
+
+/// A way to open a file: a configured rule, or a command typed into the
+/// "Open with" menu.
+#[derive(Clone)]
+pub struct Opener {
+    pub name: String,pub command: String,pub terminal: bool,
+}
+impl Opener {
+    /// The command line for `path`, with `%f` expanded or the quoted path
+    /// appended when the command doesn't mention it.
+    pub fn command_for(&self, path: &Path) -> String {
+        let quoted = previewer::shell_quote(&path.to_string_lossy());
+        if self.command.contains("%f") {
+            previewer::expand_placeholders(&self.command, |c| (c == 'f').then(|| quoted.clone()))
+        } else {
+            format!("{} {}", self.command, quoted)
+        }
+    }
+}
+
+struct Rule {
+    matcher: FileMatcher,opener: Opener,default: bool,
+}
+
+/// Openers from the config file, in the order they were written.
+pub struct OpenerRegistry {
+    rules: Vec<Rule>,
+}
+impl OpenerRegistry {
+    pub fn new(configs: &[OpenerConfig]) -> Result<Self> {
+        let mut rules = Vec::new();
+        for config in configs {
+            rules.push(Rule {
+                matcher: FileMatcher::new(&config.mime, &config.extensions, &config.globs, "opener")?,
+                opener: Opener {
+                    name: config.name.clone().unwrap_or_else(|| config.command.clone()),
+                    command: config.command.clone(),
+                    terminal: config.terminal,
+                },
+                default: config.default,
+            });
+        }
+        Ok(Self { rules })
+    }
+    /// Every opener configured for the file, default first.
+    pub fn matching(&self, path: &Path, mime: &str) -> Vec<Opener> {
+        let mut matches: Vec<&Rule> = self.rules.iter().filter(|rule| rule.matcher.matches(path, mime)).collect();
+        // Stable, so openers keep their config order otherwise.
+        matches.sort_by_key(|rule| !rule.default);
+        matches.into_iter().map(|rule| rule.opener.clone()).collect()
+    }
+    /// The opener marked `default` among the matches, else the first match.
+    /// `None` leaves the file to the system's default application.
+    pub fn default_for(&self, path: &Path, mime: &str) -> Option<Opener> {
+        self.matching(path, mime).into_iter().next()
+    }
+}
+
+/// Starts a GUI program without waiting for it or sharing the terminal.
+pub fn spawn_detached(command: &str) -> Result<()> {
+    let mut cmd = previewer::shell_command(command);
+    cmd.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+    // Its own process group, so Ctrl-C in the terminal doesn't reach it.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+    let mut child = cmd.spawn().with_context(|| format!("Cannot run '{}'", command))?;
+    // Reap it whenever it exits so it doesn't linger as a zombie.
+    thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,207 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use regex::Regex;
+use unicode_width::UnicodeWidthChar;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PagerPrompt {
+    Search,GotoLine,
+}
+
+/// Full-screen viewer over the same lines the preview pane would show,
+/// without its size limits.
+pub struct Pager {
+    pub path: PathBuf,pub title: String,pub lines: Vec<Line<'static>>,pub top: usize,pub left: usize,pub cursor: usize,pub anchor: Option<usize>,pub wrap: bool,pub search: Option<Regex>,pub prompt: Option<(PagerPrompt, String)>,pub message: Option<String>,
+    /// Rows available for lines, updated on every draw.
+    pub height: usize,
+    plain: Vec<String>,
+}
+impl Pager {
+    pub fn new(path: PathBuf, title: String, lines: Vec<Line<'static>>) -> Self {
+        let plain = lines.iter().map(|line| line.spans.iter().map(|span| span.content.as_ref()).collect()).collect();
+        Self {
+            path,title,lines,top: 0,left: 0,cursor: 0,anchor: None,wrap: false,search: None,prompt: None,message: None,height: 1,plain,
+        }
+    }
+    pub fn plain(&self, index: usize) -> &str {
+        &self.plain[index]
+    }
+    /// Moves the cursor by `delta` lines, scrolling to keep it visible.
+    pub fn move_cursor(&mut self, delta: isize) {
+        let last = self.lines.len().saturating_sub(1);
+        self.cursor = self.cursor.saturating_add_signed(delta).min(last);
+        self.scroll_to_cursor();
+    }
+    pub fn goto_line(&mut self, line: usize) {
+        self.cursor = line.saturating_sub(1).min(self.lines.len().saturating_sub(1));
+        self.top = self.cursor.saturating_sub(self.height / 2);
+    }
+    fn scroll_to_cursor(&mut self) {
+        if self.cursor < self.top {
+            self.top = self.cursor;
+        } else if self.cursor >= self.top + self.height {
+            self.top = self.cursor + 1 - self.height;
+        }
+    }
+    /// Compiles `pattern`, ignoring case unless it has an uppercase letter,
+    /// and jumps to the first match at or after the cursor.
+    pub fn set_search(&mut self, pattern: &str) -> Result<(), regex::Error> {
+        if pattern.is_empty() {
+            self.search = None;
+            return Ok(());
+        }
+        let case = if pattern.chars().any(char::is_uppercase) { "" } else { "(?i)" };
+        self.search = Some(Regex::new(&format!("{}{}", case, pattern))?);
+        self.find(true, true);
+        Ok(())
+    }
+    /// Moves to the next (or previous) line matching the search, wrapping
+    /// around the ends. `include_current` also considers the cursor line.
+    pub fn find(&mut self, forward: bool, include_current: bool) {
+        let Some(regex) = &self.search else {
+            self.message = Some("No search pattern; press / to search".to_string());
+            return;
+        };
+        let count = self.lines.len();
+        if count == 0 {
+            self.message = Some(format!("Pattern not found: {}", regex));
+            return;
+        }
+        let start = if include_current { 0 } else { 1 };
+        let found = (start..start + count)
+            .map(|step| if forward { (self.cursor + step) % count } else { (self.cursor + 2 * count - step) % count })
+            .find(|&index| regex.is_match(&self.plain[index]));
+        match found {
+            Some(index) => {
+                self.cursor = index;
+                self.top = index.saturating_sub(self.height / 3);
+                self.message = None;
+            }
+            None => self.message = Some(format!("Pattern not found: {}", regex)),
+        }
+    }
+    /// Lines from the selection anchor to the cursor, or just the cursor
+    /// line. `None` when there are no lines at all.
+    pub fn selected_range(&self) -> Option<(usize, usize)> {
+        if self.lines.is_empty() {
+            return None;
+        }
+        let anchor = self.anchor.unwrap_or(self.cursor);
+        Some((anchor.min(self.cursor), anchor.max(self.cursor)))
+    }
+    pub fn selected_text(&self) -> Option<String> {
+        let (first, last) = self.selected_range()?;
+        Some(self.plain[first..=last].join("\n"))
+    }
+}
+
+/// Styles the parts of `line` (whose text is `plain`) that `regex` matches.
+pub fn highlight_matches(line: &Line<'static>, plain: &str, regex: &Regex) -> Line<'static> {
+    let matches: Vec<(usize, usize)> =
+        regex.find_iter(plain).filter(|found| !found.is_empty()).map(|found| (found.start(), found.end())).collect();
+    if matches.is_empty() {
+        return line.clone();
+    }
+    let highlight = Style::default().fg(Color::Black).bg(Color::Yellow);
+    let mut spans = Vec::new();
+    let mut offset = 0;
+    for span in &line.spans {
+        let text = span.content.as_ref();
+        let (start, end) = (offset, offset + text.len());
+        let mut position = start;
+        for &(match_start, match_end) in &matches {
+            let (from, to) = (match_start.max(position), match_end.min(end));
+            if from >= to {
+                continue;
+            }
+            if from > position {
+                spans.push(Span::styled(text[position - start..from - start].to_string(), span.style));
+            }
+            spans.push(Span::styled(text[from - start..to - start].to_string(), span.style.patch(highlight)));
+            position = to;
+        }
+        if position < end {
+            spans.push(Span::styled(text[position - start..].to_string(), span.style));
+        }
+        offset = end;
+    }
+    Line::from(spans).style(line.style)
+}
+
+/// Classic `offset  hex bytes  |ascii|` dump, 16 bytes per line.
+pub fn hex_lines(data: &[u8]) -> Vec<Line<'static>> {
+    data.chunks(16)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let (low, high) = hex.split_at(hex.len().min(8));
+            let ascii: String = chunk.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }).collect();
+            Line::from(vec![
+                Span::styled(format!("{:08x}  ", row * 16), Style::default().fg(Color::DarkGray)),
+                Span::raw(format!("{:<23}  {:<23}  ", low.join(" "), high.join(" "))),
+                Span::styled(format!("|{}|", ascii), Style::default().fg(Color::Cyan).add_modifier(Modifier::DIM)),
+            ])
+        })
+        .collect()
+}
+
+/// Escape sequence asking the terminal to put `text` on the system
+/// clipboard (OSC 52), which also works over SSH.
+pub fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", STANDARD.encode(text))
+}
+
+/// The part of `line` starting `start` columns in, at most `width` wide.
+pub fn slice_columns(line: &Line<'static>, start: usize, width: usize) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut column = 0;
+    for span in &line.spans {
+        let mut text = String::new();
+        for c in span.content.chars() {
+            let char_width = c.width().unwrap_or(0);
+            if column >= start && column + char_width <= start + width {
+                text.push(c);
+            }
+            column += char_width;
+        }
+        if !text.is_empty() {
+            spans.push(Span::styled(text, span.style));
+        }
+        if column >= start + width {
+            break;
+        }
+    }
+    Line::from(spans).style(line.style)
+}
+
+/// Splits `line` into rows of at most `width` columns, breaking anywhere
+/// so every row lines up with the gutter.
+pub fn wrap_columns(line: &Line<'static>, width: usize) -> Vec<Line<'static>> {
+    let mut rows = Vec::new();
+    let mut spans = Vec::new();
+    let mut column = 0;
+    for span in &line.spans {
+        let mut text = String::new();
+        for c in span.content.chars() {
+            let char_width = c.width().unwrap_or(0);
+            if column + char_width > width && column > 0 {
+                if !text.is_empty() {
+                    spans.push(Span::styled(std::mem::take(&mut text), span.style));
+                }
+                rows.push(Line::from(std::mem::take(&mut spans)).style(line.style));
+                column = 0;
+            }
+            text.push(c);
+            column += char_width;
+        }
+        if !text.is_empty() {
+            spans.push(Span::styled(text, span.style));
+        }
+    }
+    rows.push(Line::from(spans).style(line.style));
+    rows
+}
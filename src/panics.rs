@@ -0,0 +1,31 @@
+use anyhow::{anyhow, Result};
+use std::{
+    cell::Cell,
+    panic::{self, AssertUnwindSafe},
+};
+
+thread_local! {
+    /// Set while a [`quietly`] parser runs on this thread.
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Installs a panic hook that stays silent for panics inside [`quietly`]
+/// and defers to the previous hook otherwise. Called once at startup, so
+/// the hook is never swapped while other threads may panic.
+pub fn install_panic_hook() {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if !QUIET.with(Cell::get) {
+            hook(info);
+        }
+    }));
+}
+
+/// Runs a parser that may panic on malformed input without the panic
+/// message being printed over the UI.
+pub fn quietly<T>(parse: impl FnOnce() -> Result<T>) -> Result<T> {
+    let was_quiet = QUIET.with(|quiet| quiet.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(parse));
+    QUIET.with(|quiet| quiet.set(was_quiet));
+    result.unwrap_or_else(|_| Err(anyhow!("The parser crashed on this file")))
+}
@@ -0,0 +1,261 @@
+use crate::config::PreviewerConfig;
+use ansi_to_tui::IntoText;
+use anyhow::{bail, Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ratatui::text::Text;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// A file version: a new modification time or size means a new preview.
+#[derive(Clone, Hash, PartialEq, Eq)]
+struct CacheKey {
+    path: PathBuf,modified: Option<SystemTime>,size: u64,
+}
+
+/// Which files a configured command applies to: any of its MIME
+/// patterns, extensions or globs matching is enough.
+pub struct FileMatcher {
+    mimes: Vec<String>,extensions: Vec<String>,globs: GlobSet,
+}
+impl FileMatcher {
+    /// `kind` names the config section in error messages.
+    pub fn new(mimes: &[String], extensions: &[String], globs: &[String], kind: &str) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in globs {
+            builder.add(Glob::new(pattern).with_context(|| format!("Invalid {} glob '{}'", kind, pattern))?);
+        }
+        Ok(Self { mimes: mimes.to_vec(), extensions: extensions.to_vec(), globs: builder.build()? })
+    }
+    pub fn matches(&self, path: &Path, mime: &str) -> bool {
+        let extension = path.extension().and_then(|ext| ext.to_str()).map(str::to_lowercase);
+        let by_extension = extension.is_some_and(|ext| self.extensions.iter().any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&ext)));
+        let by_glob = path.file_name().is_some_and(|name| self.globs.is_match(name)) || self.globs.is_match(path);
+        let by_mime = self.mimes.iter().any(|pattern| mime_matches(pattern, mime));
+        by_extension || by_glob || by_mime
+    }
+}
+
+struct Previewer {
+    matcher: FileMatcher,command: String,timeout: Duration,
+}
+/// `image/*` matches every image type, anything else must match exactly.
+fn mime_matches(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(kind) => mime.split('/').next() == Some(kind),
+        None => pattern.eq_ignore_ascii_case(mime),
+    }
+}
+
+pub enum ExternalPreview<'a> {
+    Running(&'a str),Done(&'a Text<'static>),Failed(&'a str),
+}
+
+/// User configured preview commands. Commands run on a background thread
+/// and their output is kept per file version so each runs once.
+pub struct PreviewerRegistry {
+    previewers: Vec<Previewer>,
+    results: HashMap<CacheKey, Result<Text<'static>, String>>,
+    pending: HashSet<CacheKey>,
+    sender: Sender<(CacheKey, Result<Text<'static>, String>)>,
+    receiver: Receiver<(CacheKey, Result<Text<'static>, String>)>,
+}
+impl PreviewerRegistry {
+    pub fn new(configs: &[PreviewerConfig]) -> Result<Self> {
+        let mut previewers = Vec::new();
+        for config in configs {
+            previewers.push(Previewer {
+                matcher: FileMatcher::new(&config.mime, &config.extensions, &config.globs, "previewer")?,
+                command: config.command.clone(),
+                timeout: Duration::from_millis(config.timeout_ms),
+            });
+        }
+        let (sender, receiver) = mpsc::channel();
+        Ok(Self { previewers, results: HashMap::new(), pending: HashSet::new(), sender, receiver })
+    }
+    /// Preview of `path` from the first matching previewer, starting its
+    /// command if needed. `None` when no previewer is configured for the file.
+    pub fn preview(&mut self, path: &Path, mime: &str, width: u16, height: u16) -> Option<ExternalPreview<'_>> {
+        let previewer = self.previewers.iter().find(|p| p.matcher.matches(path, mime))?;
+        let metadata = fs::metadata(path).ok()?;
+        let key = CacheKey { path: path.to_path_buf(), modified: metadata.modified().ok(), size: metadata.len() };
+        if let Some(result) = self.results.get(&key) {
+            return Some(match result {
+                Ok(text) => ExternalPreview::Done(text),
+                Err(err) => ExternalPreview::Failed(err),
+            });
+        }
+        if self.pending.insert(key.clone()) {
+            // `%f` is the quoted file path, `%w`/`%h` the preview size in cells.
+            let command = expand_placeholders(&previewer.command, |c| match c {
+                'f' => Some(shell_quote(&path.to_string_lossy())),
+                'w' => Some(width.to_string()),
+                'h' => Some(height.to_string()),
+                _ => None,
+            });
+            let timeout = previewer.timeout;
+            let sender = self.sender.clone();
+            thread::spawn(move || {
+                let result = run_command(&command, timeout).map_err(|err| format!("{:#}", err));
+                let _ = sender.send((key, result));
+            });
+        }
+        Some(ExternalPreview::Running(&previewer.command))
+    }
+    /// Collects output from finished commands.
+    pub fn poll(&mut self) {
+        const MAX_CACHED: usize = 128;
+        while let Ok((key, result)) = self.receiver.try_recv() {
+            self.pending.remove(&key);
+            if self.results.len() >= MAX_CACHED {
+                self.results.clear();
+            }
+            self.results.insert(key, result);
+        }
+    }
+}
+
+/// Replaces `%` followed by a letter with what `lookup` returns for it and
+/// `%%` with a literal percent sign. Unknown letters are kept as they are.
+pub fn expand_placeholders(command: &str, lookup: impl Fn(char) -> Option<String>) -> String {
+    let mut expanded = String::new();
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => expanded.push('%'),
+            Some(other) => match lookup(other) {
+                Some(value) => expanded.push_str(&value),
+                None => {
+                    expanded.push('%');
+                    expanded.push(other);
+                }
+            },
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+pub fn shell_quote(value: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", value.replace('"', "\\\""))
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+pub fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+fn run_command(command: &str, timeout: Duration) -> Result<Text<'static>> {
+    const MAX_OUTPUT_BYTES: u64 = 1024 * 1024;
+    let mut cmd = shell_command(command);
+    cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).env("TERM", "xterm-256color");
+    // Its own process group, so a timeout stops whatever the shell started too.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("Cannot run '{}'", command))?;
+    // Read both pipes on their own threads so a chatty command can't fill
+    // one and block while we wait for it to exit.
+    let stdout = read_pipe(child.stdout.take(), MAX_OUTPUT_BYTES);
+    let stderr = read_pipe(child.stderr.take(), MAX_OUTPUT_BYTES);
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            kill_group(&mut child);
+            let _ = child.wait();
+            bail!("Previewer timed out after {} ms", timeout.as_millis());
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    let (stdout, _) = stdout.join().unwrap_or_default();
+    let (stderr, _) = stderr.join().unwrap_or_default();
+    if stdout.is_empty() && !status.success() {
+        let message = String::from_utf8_lossy(&stderr).trim().to_string();
+        bail!("Previewer exited with {}: {}", status, message);
+    }
+    stdout.into_text().context("Cannot decode previewer output")
+}
+/// Reads up to `max_bytes` of a child's pipe on its own thread, then
+/// discards the rest so the child never blocks on a full pipe. The flag
+/// is set when something was discarded.
+pub fn read_pipe(pipe: Option<impl Read + Send + 'static>, max_bytes: u64) -> JoinHandle<(Vec<u8>, bool)> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        let Some(mut pipe) = pipe else {
+            return (output, false);
+        };
+        let _ = pipe.by_ref().take(max_bytes).read_to_end(&mut output);
+        let discarded = io::copy(&mut pipe, &mut io::sink()).unwrap_or(0);
+        (output, discarded > 0)
+    })
+}
+/// Kills a child spawned as a process group leader along with everything
+/// it started.
+#[cfg(unix)]
+pub fn kill_group(child: &mut Child) {
+    // SAFETY: killpg only sends a signal; the group id is the child's pid
+    // since it was spawned as a group leader.
+    unsafe {
+        libc::killpg(child.id() as libc::pid_t, libc::SIGKILL);
+    }
+}
+#[cfg(not(unix))]
+pub fn kill_group(child: &mut Child) {
+    let _ = child.kill();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(c: char) -> Option<String> {
+        (c == 'f').then(|| "file".to_string())
+    }
+
+    #[test]
+    fn expand_placeholders_replaces_known_letters() {
+        assert_eq!(expand_placeholders("cat %f", lookup), "cat file");
+        assert_eq!(expand_placeholders("%f%f", lookup), "filefile");
+    }
+
+    #[test]
+    fn expand_placeholders_keeps_escapes_and_unknown_letters() {
+        assert_eq!(expand_placeholders("100%% of %f", lookup), "100% of file");
+        assert_eq!(expand_placeholders("%%f", lookup), "%f");
+        assert_eq!(expand_placeholders("date +%Y", lookup), "date +%Y");
+        assert_eq!(expand_placeholders("trailing %", lookup), "trailing %");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shell_quote_survives_single_quotes() {
+        assert_eq!(shell_quote("plain"), "'plain'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        let output = shell_command(&format!("printf %s {}", shell_quote("a 'b' $c"))).output().unwrap();
+        assert_eq!(output.stdout, b"a 'b' $c");
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{jobs::JobManager, previewer};
+use anyhow::{bail, Context, Result};
+use ratatui::{
+    style::{Color, Style},
+    text::Line,
+};
+use std::{
+    cell::Cell,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// What a `!` command printed and how it exited. The output is split into
+/// lines once when it arrives, stderr in red after stdout.
+pub struct ShellOutput {
+    pub command: String,pub status: String,pub success: bool,pub lines: Vec<Line<'static>>,
+}
+
+/// Expands the placeholders of a `!` command: `%f` the selected files,
+/// `%d` the current directory and `%c` the clipboard, each shell-quoted.
+pub fn expand(command: &str, files: &[PathBuf], dir: &Path, clipboard: Option<&Path>) -> Result<String> {
+    let quote = |path: &Path| previewer::shell_quote(&path.to_string_lossy());
+    let missing = Cell::new(None);
+    let expanded = previewer::expand_placeholders(command, |c| match c {
+        'f' if files.is_empty() => {
+            missing.set(Some("No files selected for %f"));
+            None
+        }
+        'f' => Some(files.iter().map(|file| quote(file)).collect::<Vec<_>>().join(" ")),
+        'd' => Some(quote(dir)),
+        'c' => match clipboard {
+            Some(path) => Some(quote(path)),
+            None => {
+                missing.set(Some("No file on the clipboard for %c"));
+                None
+            }
+        },
+        _ => None,
+    });
+    if let Some(message) = missing.get() {
+        bail!(message);
+    }
+    Ok(expanded)
+}
+
+/// Runs `!` commands as background jobs and hands their output back.
+pub struct ShellRunner {
+    sender: Sender<ShellOutput>,receiver: Receiver<ShellOutput>,
+    /// Cancel flags of the commands started so far; finished ones are
+    /// dropped when cancelling.
+    running: Vec<Arc<AtomicBool>>,
+}
+impl ShellRunner {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { sender, receiver, running: Vec::new() }
+    }
+    /// Stops every `!` command still running, returning how many there were.
+    pub fn cancel_all(&mut self) -> usize {
+        // Only the job thread holds the other reference while it runs.
+        self.running.retain(|cancel| Arc::strong_count(cancel) > 1);
+        for cancel in &self.running {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        std::mem::take(&mut self.running).len()
+    }
+    pub fn spawn(&mut self, jobs: &mut JobManager, command: String, dir: PathBuf) {
+        const MAX_OUTPUT_BYTES: u64 = 1024 * 1024;
+        let sender = self.sender.clone();
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.running.push(Arc::clone(&cancel));
+        jobs.spawn(format!("Running '{}'", command), dir.clone(), move |_progress| {
+            let mut cmd = previewer::shell_command(&command);
+            cmd.current_dir(&dir).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+            // Its own process group, so cancelling stops whatever it started too.
+            #[cfg(unix)]
+            std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+            let mut child = cmd.spawn().with_context(|| format!("Cannot run '{}'", command))?;
+            let stdout = previewer::read_pipe(child.stdout.take(), MAX_OUTPUT_BYTES);
+            let stderr = previewer::read_pipe(child.stderr.take(), MAX_OUTPUT_BYTES);
+            let exit_status = loop {
+                if let Some(status) = child.try_wait().with_context(|| format!("Cannot run '{}'", command))? {
+                    break status;
+                }
+                if cancel.load(Ordering::Relaxed) {
+                    previewer::kill_group(&mut child);
+                    let _ = child.wait();
+                    return Ok(format!("Cancelled '{}'", command));
+                }
+                thread::sleep(Duration::from_millis(10));
+            };
+            let (stdout, stdout_cut) = stdout.join().unwrap_or_default();
+            let (stderr, stderr_cut) = stderr.join().unwrap_or_default();
+            let mut lines: Vec<Line<'static>> =
+                String::from_utf8_lossy(&stdout).lines().map(|line| Line::raw(line.to_string())).collect();
+            let error_style = Style::default().fg(Color::Red);
+            lines.extend(String::from_utf8_lossy(&stderr).lines().map(|line| Line::styled(line.to_string(), error_style)));
+            let note_style = Style::default().fg(Color::DarkGray);
+            if stdout_cut || stderr_cut {
+                lines.push(Line::styled("(output cut off after 1 MiB)", note_style));
+            }
+            if lines.is_empty() {
+                lines.push(Line::styled("(no output)", note_style));
+            }
+            let status = exit_status.to_string();
+            let _ = sender.send(ShellOutput { command, status: status.clone(), success: exit_status.success(), lines });
+            Ok(format!("Command finished with {}", status))
+        });
+    }
+    pub fn poll(&self) -> Option<ShellOutput> {
+        self.receiver.try_recv().ok()
+    }
+}
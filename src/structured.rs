@@ -0,0 +1,288 @@
+use anyhow::{Context, Result};
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DataFormat {
+    Json,Yaml,Toml,
+}
+impl DataFormat {
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        match mime {
+            "application/json" => Some(Self::Json),
+            "application/yaml" => Some(Self::Yaml),
+            "application/toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Json => "JSON",
+            Self::Yaml => "YAML",
+            Self::Toml => "TOML",
+        }
+    }
+}
+
+/// Format-neutral document tree so JSON, YAML and TOML share one renderer.
+pub enum Node {
+    Null,Bool(bool),Number(String),String(String),List(Vec<Node>),Map(Vec<(String, Node)>),
+}
+impl Node {
+    /// Number of nesting levels below this node; scalars have depth 0.
+    pub fn depth(&self) -> usize {
+        match self {
+            Self::List(items) => 1 + items.iter().map(Node::depth).max().unwrap_or(0),
+            Self::Map(entries) => 1 + entries.iter().map(|(_, node)| node.depth()).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+    fn from_json(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Self::Null,
+            serde_json::Value::Bool(b) => Self::Bool(b),
+            serde_json::Value::Number(n) => Self::Number(n.to_string()),
+            serde_json::Value::String(s) => Self::String(s),
+            serde_json::Value::Array(items) => Self::List(items.into_iter().map(Self::from_json).collect()),
+            serde_json::Value::Object(map) => Self::Map(map.into_iter().map(|(k, v)| (k, Self::from_json(v))).collect()),
+        }
+    }
+    fn from_yaml(value: serde_yaml::Value) -> Self {
+        match value {
+            serde_yaml::Value::Null => Self::Null,
+            serde_yaml::Value::Bool(b) => Self::Bool(b),
+            serde_yaml::Value::Number(n) => Self::Number(n.to_string()),
+            serde_yaml::Value::String(s) => Self::String(s),
+            serde_yaml::Value::Sequence(items) => Self::List(items.into_iter().map(Self::from_yaml).collect()),
+            serde_yaml::Value::Mapping(map) => Self::Map(
+                map.into_iter()
+                    .map(|(k, v)| {
+                        let key = match k {
+                            serde_yaml::Value::String(s) => s,
+                            other => serde_yaml::to_string(&other).unwrap_or_default().trim_end().to_string(),
+                        };
+                        (key, Self::from_yaml(v))
+                    })
+                    .collect(),
+            ),
+            serde_yaml::Value::Tagged(tagged) => Self::from_yaml(tagged.value),
+        }
+    }
+    fn from_toml(value: toml::Value) -> Self {
+        match value {
+            toml::Value::String(s) => Self::String(s),
+            toml::Value::Integer(i) => Self::Number(i.to_string()),
+            toml::Value::Float(f) => Self::Number(f.to_string()),
+            toml::Value::Boolean(b) => Self::Bool(b),
+            toml::Value::Datetime(d) => Self::Number(d.to_string()),
+            toml::Value::Array(items) => Self::List(items.into_iter().map(Self::from_toml).collect()),
+            toml::Value::Table(table) => Self::Map(table.into_iter().map(|(k, v)| (k, Self::from_toml(v))).collect()),
+        }
+    }
+}
+
+/// A parse failure with the 1-based line it points at, when known.
+pub struct ParseError {
+    pub message: String,pub line: Option<usize>,
+}
+
+pub fn parse(text: &str, format: DataFormat) -> Result<Node, ParseError> {
+    match format {
+        DataFormat::Json => serde_json::from_str(&strip_jsonc(text))
+            .map(Node::from_json)
+            .map_err(|err| ParseError { message: err.to_string(), line: Some(err.line()) }),
+        DataFormat::Yaml => {
+            let mut documents = Vec::new();
+            for document in serde_yaml::Deserializer::from_str(text) {
+                let value = serde_yaml::Value::deserialize(document).map_err(|err: serde_yaml::Error| ParseError {
+                    message: err.to_string(),
+                    line: err.location().map(|location| location.line()),
+                })?;
+                documents.push(Node::from_yaml(value));
+            }
+            // A stream with several `---` documents is shown as a list of them.
+            Ok(if documents.len() == 1 { documents.remove(0) } else { Node::List(documents) })
+        }
+        DataFormat::Toml => text
+            .parse::<toml::Table>()
+            .map(|table| Node::from_toml(toml::Value::Table(table)))
+            .map_err(|err| ParseError {
+                message: err.message().to_string(),
+                line: err.span().map(|span| text[..span.start.min(text.len())].matches('\n').count() + 1),
+            }),
+    }
+}
+
+/// Blanks out the `//` and `/* */` comments and trailing commas that JSONC
+/// files (`.jsonc`, `tsconfig.json`) allow, so plain JSON parsing accepts
+/// them. Newlines are kept so errors point at the right line.
+fn strip_jsonc(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            stripped.push(c);
+            match c {
+                '\\' => stripped.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                while chars.next_if(|&next| next != '\n').is_some() {}
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        stripped.push('\n');
+                    }
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+            }
+            _ => {
+                in_string = c == '"';
+                stripped.push(c);
+            }
+        }
+    }
+    // Comments are gone, so a comma followed only by whitespace and a
+    // closing bracket is a trailing one.
+    let mut bytes = stripped.into_bytes();
+    let mut in_string = false;
+    let mut escaped = false;
+    for index in 0..bytes.len() {
+        match bytes[index] {
+            _ if escaped => escaped = false,
+            b'\\' if in_string => escaped = true,
+            b'"' => in_string = !in_string,
+            b',' if !in_string => {
+                let next = bytes[index + 1..].iter().find(|byte| !byte.is_ascii_whitespace());
+                if matches!(next, Some(b'}' | b']')) {
+                    bytes[index] = b' ';
+                }
+            }
+            _ => {}
+        }
+    }
+    String::from_utf8(bytes).expect("only ASCII bytes were replaced")
+}
+
+/// Renders `node` as an indented tree. Containers nested deeper than
+/// `fold_depth` are collapsed to a one-line summary.
+pub fn tree_lines(node: &Node, fold_depth: usize, max_lines: usize) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    push_node(&mut lines, node, None, 0, fold_depth, max_lines);
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        lines.push(Line::styled("…", Style::default().fg(Color::DarkGray)));
+    }
+    lines
+}
+fn push_node(lines: &mut Vec<Line<'static>>, node: &Node, label: Option<Span<'static>>, depth: usize, fold_depth: usize, max_lines: usize) {
+    if lines.len() > max_lines {
+        return;
+    }
+    let marker_style = Style::default().fg(Color::DarkGray);
+    let mut spans = vec![Span::raw("  ".repeat(depth))];
+    let children: Vec<(Span<'static>, &Node)> = match node {
+        Node::List(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (Span::styled(format!("{}: ", i), marker_style), item))
+            .collect(),
+        Node::Map(entries) => entries
+            .iter()
+            .map(|(key, value)| (Span::styled(format!("{}: ", key), Style::default().fg(Color::Cyan)), value))
+            .collect(),
+        scalar => {
+            spans.extend(label);
+            spans.push(scalar_span(scalar));
+            lines.push(Line::from(spans));
+            return;
+        }
+    };
+    let (open, unit) = match node {
+        Node::List(_) => ("[]", if children.len() == 1 { "item" } else { "items" }),
+        _ => ("{}", if children.len() == 1 { "key" } else { "keys" }),
+    };
+    let collapsed = depth >= fold_depth && !children.is_empty();
+    spans.push(Span::styled(if collapsed { "▸ " } else { "▾ " }, marker_style));
+    spans.extend(label);
+    spans.push(Span::styled(format!("{} {} {}", open, children.len(), unit), marker_style));
+    lines.push(Line::from(spans));
+    if collapsed {
+        return;
+    }
+    for (label, child) in children {
+        push_node(lines, child, Some(label), depth + 1, fold_depth, max_lines);
+    }
+}
+fn scalar_span(node: &Node) -> Span<'static> {
+    match node {
+        Node::Null => Span::styled("null", Style::default().fg(Color::Blue).add_modifier(Modifier::ITALIC)),
+        Node::Bool(b) => Span::styled(b.to_string(), Style::default().fg(Color::Blue)),
+        Node::Number(n) => Span::styled(n.clone(), Style::default().fg(Color::Magenta)),
+        Node::String(s) => Span::styled(format!("{:?}", s), Style::default().fg(Color::Green)),
+        Node::List(_) | Node::Map(_) => Span::raw(""),
+    }
+}
+
+/// Field delimiter for delimited text tables, `None` for other types.
+pub fn table_delimiter(mime: &str) -> Option<u8> {
+    match mime {
+        "text/csv" => Some(b','),
+        "text/tab-separated-values" => Some(b'\t'),
+        _ => None,
+    }
+}
+
+pub struct TableData {
+    pub header: Vec<String>,pub rows: Vec<Vec<String>>,pub widths: Vec<u16>,pub encoding: String,
+}
+/// Reads the header and up to `max_rows` records.
+pub fn read_table(path: &Path, delimiter: u8, max_rows: usize) -> Result<TableData> {
+    const MAX_READ_BYTES: u64 = 1024 * 1024;
+    let decoded = crate::encoding::read_file(path, MAX_READ_BYTES)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(decoded.text.as_bytes());
+    let header: Vec<String> = reader.headers().context("Cannot read header row")?.iter().map(str::to_string).collect();
+    let mut rows = Vec::new();
+    for record in reader.records().take(max_rows) {
+        let record = record.context("Malformed record")?;
+        rows.push(record.iter().map(|field| field.replace(['\n', '\r'], " ")).collect::<Vec<_>>());
+    }
+    let widths = column_widths(&header, &rows);
+    Ok(TableData { header, rows, widths, encoding: decoded.summary() })
+}
+
+/// Widths that fit the longest cell in each column, capped so one wide
+/// column can't push out the rest.
+pub fn column_widths(header: &[String], rows: &[Vec<String>]) -> Vec<u16> {
+    const MAX_COLUMN_WIDTH: usize = 40;
+    let columns = rows.iter().map(Vec::len).chain([header.len()]).max().unwrap_or(0);
+    (0..columns)
+        .map(|column| {
+            std::iter::once(header)
+                .chain(rows.iter().map(Vec::as_slice))
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+                .clamp(1, MAX_COLUMN_WIDTH) as u16
+        })
+        .collect()
+}